@@ -0,0 +1,25 @@
+//! Records the commit being built, which `GET /version` reports.
+
+use std::process::Command;
+
+fn main() {
+    println!("cargo:rerun-if-env-changed=NUKO_GIT_COMMIT");
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+
+    // Builds from a source archive have no repository to ask
+    let commit = std::env::var("NUKO_GIT_COMMIT")
+        .ok()
+        .or_else(|| {
+            let output = Command::new("git")
+                .args(["rev-parse", "--short=12", "HEAD"])
+                .output()
+                .ok()?;
+            output
+                .status
+                .success()
+                .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        })
+        .unwrap_or_else(|| "unknown".into());
+    println!("cargo:rustc-env=NUKO_GIT_COMMIT={}", commit);
+}
@@ -0,0 +1,105 @@
+//! Operator-only endpoints, authorized with `Authorization: Bearer {ADMIN_TOKEN}`.
+//!
+//! They are disabled entirely while `ADMIN_TOKEN` is not configured.
+
+use worker::{Request, Response, RouteContext};
+
+use crate::analytics::{self, DailyStats, RETENTION_DAYS};
+use crate::audit::AuditEntry;
+use crate::cache;
+use crate::circuit_breaker::CircuitState;
+use crate::command::GuildCommandSync;
+use crate::context::RouteData;
+use crate::error_report::ReportThrottle;
+use crate::guild_config::GuildConfig;
+use crate::job::JobRun;
+use crate::preferences::UserPreferences;
+use crate::store::migration::{migrate, purge_retired};
+use crate::store::{WorkersKvStore, NAMESPACE};
+
+pub(crate) fn is_authorized(req: &Request, ctx: &RouteContext<RouteData>) -> bool {
+    let provided = req.headers().get("authorization").ok().flatten();
+    bearer_matches(ctx.data.config.admin_token.as_deref(), provided.as_deref())
+}
+
+/// Whether an `Authorization` header carries the admin token, never when no
+/// token is configured.
+pub(crate) fn bearer_matches(token: Option<&str>, authorization: Option<&str>) -> bool {
+    match token {
+        Some(token) if !token.is_empty() => constant_time_eq(
+            authorization.unwrap_or_default().as_bytes(),
+            format!("Bearer {}", token).as_bytes(),
+        ),
+        _ => false,
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Upgrades every stored record to its current version, `POST /admin/migrate`.
+pub(crate) async fn migrate_records(
+    req: Request,
+    ctx: RouteContext<RouteData>,
+) -> worker::Result<Response> {
+    if !is_authorized(&req, &ctx) {
+        return Response::error("Unauthorized", 401);
+    }
+
+    let store = WorkersKvStore::new(ctx.kv(NAMESPACE)?);
+    let reports = vec![
+        migrate::<UserPreferences>(&store).await,
+        migrate::<GuildConfig>(&store).await,
+        migrate::<DailyStats>(&store).await,
+        migrate::<AuditEntry>(&store).await,
+        migrate::<CircuitState>(&store).await,
+        migrate::<ReportThrottle>(&store).await,
+        migrate::<JobRun>(&store).await,
+        migrate::<GuildCommandSync>(&store).await,
+        cache::migrate_entries(&store).await,
+        // Caches from before the shared cache layer
+        purge_retired(&store, "rate cache", "cache:").await,
+        purge_retired(&store, "timeseries cache", "timeseries_cache:").await,
+    ]
+    .into_iter()
+    .collect::<Result<Vec<_>, _>>();
+
+    match reports {
+        Ok(reports) => {
+            log_info!("Ran record migrations");
+            Response::from_json(&reports)
+        }
+        Err(err) => {
+            log_error!("Record migration failed: {}", err);
+            Response::error(err.to_string(), 500)
+        }
+    }
+}
+
+/// The raw usage counters by day, `GET /admin/stats?days={days}`.
+pub(crate) async fn usage_stats(
+    req: Request,
+    ctx: RouteContext<RouteData>,
+) -> worker::Result<Response> {
+    if !is_authorized(&req, &ctx) {
+        return Response::error("Unauthorized", 401);
+    }
+
+    let days = req
+        .url()?
+        .query_pairs()
+        .find(|(key, _)| key == "days")
+        .and_then(|(_, days)| days.parse::<i64>().ok())
+        .unwrap_or(7)
+        .clamp(1, RETENTION_DAYS);
+
+    let store = WorkersKvStore::new(ctx.kv(NAMESPACE)?);
+    match analytics::load_days(&store, days).await {
+        Ok(stats) => Response::from_json(&stats),
+        Err(err) => {
+            log_error!("Unable to load usage stats: {}", err);
+            Response::error(err.to_string(), 500)
+        }
+    }
+}
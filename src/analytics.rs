@@ -0,0 +1,154 @@
+//! Usage counters, aggregated per day in the [`Store`].
+//!
+//! Each day is split over a few shards that are merged when read, since KV only
+//! accepts about one write per second to the same key. Updates are
+//! read-modify-write, so concurrent ones can occasionally be lost: the numbers
+//! are meant to show trends, not to be exact. Nothing here is per user.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::cache::CacheCounts;
+use crate::context::CommandContext;
+use crate::store::migration::{read_record, write_record, Record};
+use crate::store::{Store, StoreError};
+use crate::utils::random_u64;
+
+const SHARDS: i64 = 8;
+/// How long daily buckets are kept, in days.
+pub(crate) const RETENTION_DAYS: i64 = 90;
+
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+pub(crate) struct DailyStats {
+    #[serde(default)]
+    pub(crate) commands: BTreeMap<String, u64>,
+    #[serde(default)]
+    pub(crate) guilds: BTreeMap<String, u64>,
+    #[serde(default)]
+    pub(crate) pairs: BTreeMap<String, u64>,
+    #[serde(default)]
+    pub(crate) cache: BTreeMap<String, CacheCounts>,
+}
+
+impl Record for DailyStats {
+    const KIND: &'static str = "daily stats";
+    const PREFIX: &'static str = "analytics:";
+    const VERSION: u32 = 1;
+    const TTL: Option<u64> = Some((RETENTION_DAYS * 24 * 60 * 60) as u64);
+
+    fn upgrade(version: u32, _value: Value) -> Result<Value, StoreError> {
+        Err(StoreError::UnsupportedVersion(Self::KIND.into(), version))
+    }
+}
+
+fn add(counters: &mut BTreeMap<String, u64>, key: &str, count: u64) {
+    *counters.entry(key.to_string()).or_default() += count;
+}
+
+impl DailyStats {
+    pub(crate) fn merge(&mut self, other: &DailyStats) {
+        for (command, count) in &other.commands {
+            add(&mut self.commands, command, *count);
+        }
+        for (guild, count) in &other.guilds {
+            add(&mut self.guilds, guild, *count);
+        }
+        for (pair, count) in &other.pairs {
+            add(&mut self.pairs, pair, *count);
+        }
+        for (namespace, counts) in &other.cache {
+            let total = self.cache.entry(namespace.clone()).or_default();
+            total.hits += counts.hits;
+            total.stale += counts.stale;
+            total.misses += counts.misses;
+        }
+    }
+
+    /// The `limit` largest counters, largest first.
+    pub(crate) fn top(counters: &BTreeMap<String, u64>, limit: usize) -> Vec<(&str, u64)> {
+        let mut top = counters
+            .iter()
+            .map(|(key, count)| (key.as_str(), *count))
+            .collect::<Vec<_>>();
+        top.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        top.truncate(limit);
+        top
+    }
+}
+
+fn day_key(day: &str, shard: i64) -> String {
+    format!("{}{}:{}", DailyStats::PREFIX, day, shard)
+}
+
+/// The last `days` days, today first, as `YYYY-MM-DD`.
+pub(crate) fn recent_days(days: i64) -> Vec<String> {
+    let today = chrono::Utc::today().naive_utc();
+    (0..days)
+        .map(|days| {
+            (today - chrono::Duration::days(days))
+                .format("%Y-%m-%d")
+                .to_string()
+        })
+        .collect()
+}
+
+/// Notes a currency pair used by the current command.
+pub(crate) fn record_pair(ctx: &mut CommandContext, from: &str, to: &str) {
+    add(&mut ctx.usage_mut().pairs, &format!("{}_{}", from, to), 1);
+}
+
+/// Records the command that just ran, along with anything the command noted
+/// about itself and the cache lookups it made. The write happens in the background.
+pub(crate) fn record_command(ctx: &mut CommandContext, command: &str) {
+    let mut usage = ctx.take_usage();
+    add(&mut usage.commands, command, 1);
+    if let Some(guild_id) = ctx.guild_id() {
+        add(&mut usage.guilds, guild_id, 1);
+    }
+    usage.cache = ctx.cache_metrics().borrow().namespaces.clone();
+
+    let store = ctx.shared_store();
+    ctx.background().spawn(async move {
+        if let Err(err) = save(store.as_ref(), &usage).await {
+            log_debug!("Unable to record usage : {}", err);
+        }
+    });
+}
+
+async fn save(store: &dyn Store, usage: &DailyStats) -> Result<(), StoreError> {
+    let now = chrono::Utc::now();
+    let day = now.format("%Y-%m-%d").to_string();
+    let key = day_key(&day, (random_u64() % SHARDS as u64) as i64);
+
+    let mut stats = read_record::<DailyStats>(store, &key)
+        .await?
+        .unwrap_or_default();
+    stats.merge(usage);
+    write_record(store, &key, &stats, DailyStats::TTL).await
+}
+
+/// The counters of a single day, merged over every shard.
+pub(crate) async fn load_day(store: &dyn Store, day: &str) -> Result<DailyStats, StoreError> {
+    let mut stats = DailyStats::default();
+    for shard in 0..SHARDS {
+        if let Some(shard) = read_record::<DailyStats>(store, &day_key(day, shard)).await? {
+            stats.merge(&shard);
+        }
+    }
+    Ok(stats)
+}
+
+/// The counters of the last `days` days, by day.
+pub(crate) async fn load_days(
+    store: &dyn Store,
+    days: i64,
+) -> Result<BTreeMap<String, DailyStats>, StoreError> {
+    let mut stats = BTreeMap::new();
+    for day in recent_days(days) {
+        let day_stats = load_day(store, &day).await?;
+        stats.insert(day, day_stats);
+    }
+    Ok(stats)
+}
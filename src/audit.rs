@@ -0,0 +1,163 @@
+//! An append-only log of configuration changes.
+//!
+//! Changes to a guild's settings are logged under the guild, changes to a
+//! user's own defaults under the user, where `/mydata` picks them up. Keys
+//! sort newest first, and entries are never rewritten once stored.
+
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::context::CommandContext;
+use crate::discord::{webhook_parts, DiscordClient, MessagePayload};
+use crate::embed::{Embed, EmbedField, DEFAULT_COLOR};
+use crate::store::migration::{read_record, write_record, Record};
+use crate::store::{Store, StoreError};
+use crate::upstream::Upstream;
+use crate::user_data::UserData;
+use crate::utils::random_u64;
+
+/// Larger than any timestamp in milliseconds for a long while, so that
+/// subtracting from it makes newer keys sort first.
+const REVERSE_EPOCH_MILLIS: i64 = 9_999_999_999_999;
+/// Entries written by this isolate so far.
+static WRITTEN: AtomicU32 = AtomicU32::new(0);
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub(crate) struct AuditEntry {
+    pub(crate) at: i64,
+    pub(crate) actor_id: String,
+    pub(crate) guild_id: Option<String>,
+    pub(crate) command: String,
+    pub(crate) setting: String,
+    pub(crate) old_value: Value,
+    pub(crate) new_value: Value,
+}
+
+impl Record for AuditEntry {
+    const KIND: &'static str = "audit entry";
+    const PREFIX: &'static str = "audit:";
+    const VERSION: u32 = 1;
+
+    fn upgrade(version: u32, _value: Value) -> Result<Value, StoreError> {
+        Err(StoreError::UnsupportedVersion(Self::KIND.into(), version))
+    }
+}
+
+/// The key prefix of a guild's log.
+pub(crate) fn guild_log_prefix(guild_id: &str) -> String {
+    format!("{}guild:{}:", AuditEntry::PREFIX, guild_id)
+}
+
+fn entry_key(prefix: &str, now: chrono::DateTime<chrono::Utc>) -> String {
+    // The clock is only precise to the millisecond on Workers. A falling
+    // counter keeps this isolate's entries newest first within a millisecond,
+    // and random bits keep them apart from other isolates' entries.
+    let written = WRITTEN.fetch_add(1, Ordering::Relaxed);
+    format!(
+        "{}{:013}{:08x}{:08x}",
+        prefix,
+        REVERSE_EPOCH_MILLIS - now.timestamp_millis(),
+        u32::MAX - written,
+        random_u64() as u32
+    )
+}
+
+/// Logs a change made by the user invoking the current command, to the guild
+/// when `guild_id` is set or to the user otherwise.
+pub(crate) async fn record(
+    ctx: &CommandContext,
+    guild_id: Option<&str>,
+    command: &str,
+    setting: &str,
+    old_value: Value,
+    new_value: Value,
+) -> Result<(), StoreError> {
+    let actor_id = ctx
+        .user()
+        .map(|user| user.id.clone())
+        .unwrap_or_else(|| "unknown".into());
+    let prefix = match guild_id {
+        Some(guild_id) => guild_log_prefix(guild_id),
+        None => UserData::AuditLog.key(&actor_id),
+    };
+
+    let now = chrono::Utc::now();
+    let entry = AuditEntry {
+        at: now.timestamp(),
+        actor_id,
+        guild_id: guild_id.map(String::from),
+        command: command.into(),
+        setting: setting.into(),
+        old_value,
+        new_value,
+    };
+    write_record(ctx.store(), &entry_key(&prefix, now), &entry, None).await?;
+
+    if let Some(url) = ctx.config().audit_webhook_url.clone() {
+        let client =
+            DiscordClient::new(None).with_base_url(ctx.config().base_url(Upstream::Discord));
+        ctx.background().spawn(async move {
+            mirror(&client, &url, &entry).await;
+        });
+    }
+
+    Ok(())
+}
+
+/// The `limit` most recent entries under `prefix`, newest first.
+pub(crate) async fn recent(
+    store: &dyn Store,
+    prefix: &str,
+    limit: usize,
+) -> Result<Vec<AuditEntry>, StoreError> {
+    let mut entries = Vec::new();
+    for key in store.list(prefix, None).await?.keys.iter().take(limit) {
+        if let Some(entry) = read_record(store, key).await? {
+            entries.push(entry);
+        }
+    }
+    Ok(entries)
+}
+
+pub(crate) fn describe(entry: &AuditEntry) -> EmbedField {
+    EmbedField {
+        name: format!("/{} {}", entry.command, entry.setting),
+        value: format!(
+            "<t:{}:f> by {}\n`{}` → `{}`",
+            entry.at, entry.actor_id, entry.old_value, entry.new_value
+        ),
+        inline: Some(false),
+    }
+}
+
+/// Posts an entry to the webhook at `url`, a `.../webhooks/{id}/{token}` URL.
+async fn mirror(client: &DiscordClient, url: &str, entry: &AuditEntry) {
+    let (id, token) = match webhook_parts(url) {
+        Some(parts) => parts,
+        None => {
+            log_warn!("AUDIT_WEBHOOK_URL is not a webhook URL");
+            return;
+        }
+    };
+
+    let payload = MessagePayload {
+        embeds: Some(vec![Embed {
+            title: "Configuration changed".into(),
+            description: match &entry.guild_id {
+                Some(guild_id) => format!("In guild {}", guild_id),
+                None => "User defaults".into(),
+            },
+            fields: vec![describe(entry)],
+            thumbnail: None,
+            color: Some(DEFAULT_COLOR),
+            url: None,
+            footer: None,
+        }]),
+        ..MessagePayload::default()
+    };
+    if let Err(err) = client.execute_webhook(id, token, &payload).await {
+        log_warn!("Unable to mirror audit entry : {}", err);
+    }
+}
@@ -0,0 +1,5 @@
+//! Runs commands from the terminal, see `nuko_bot::cli`.
+
+fn main() -> std::io::Result<()> {
+    nuko_bot::cli::run()
+}
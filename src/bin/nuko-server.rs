@@ -0,0 +1,5 @@
+//! Runs the bot from a native HTTP server, see `nuko_bot::native`.
+
+fn main() -> std::io::Result<()> {
+    nuko_bot::native::run()
+}
@@ -1,56 +1,193 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+
+use crate::context::CommandContext;
 use crate::error::Error;
+use crate::error_report::{self, Failure};
+use crate::guard::RequestGuard;
 use crate::http::HttpError;
-use crate::interaction::{Interaction, InteractionResponse};
-use crate::verification::verify_signature;
-use worker::{Request, RouteContext};
+use crate::interaction::{Interaction, InteractionResponse, Outcome};
+use crate::logger::{self, Event, Level};
+use crate::store::{Store, MIN_TTL};
+use crate::verification::{
+    cached_public_keys, verify_signature, verify_timestamp, VerificationError,
+};
+
+type Body = Pin<Box<dyn Future<Output = Result<Vec<u8>, String>>>>;
+
+/// An interaction request as received by whichever server runs the bot.
+///
+/// The body is only read once the headers have been checked, so oversized or
+/// unsigned requests are turned away before anything is buffered.
+pub(crate) struct IncomingRequest {
+    method: String,
+    headers: HashMap<String, String>,
+    body: Option<Body>,
+}
+
+impl IncomingRequest {
+    pub(crate) fn new<F>(method: &str, headers: HashMap<String, String>, body: F) -> Self
+    where
+        F: Future<Output = Result<Vec<u8>, String>> + 'static,
+    {
+        IncomingRequest {
+            method: method.to_uppercase(),
+            headers: headers
+                .into_iter()
+                .map(|(key, value)| (key.to_lowercase(), value))
+                .collect(),
+            body: Some(Box::pin(body)),
+        }
+    }
+}
 
 pub struct App {
-    req: Request,
-    ctx: RouteContext<()>,
+    req: IncomingRequest,
+    ctx: CommandContext,
+    /// Logged once the interaction has been answered, filled in as it is handled.
+    event: Event,
+    /// What is reported should the interaction fail.
+    failure: Failure,
 }
 
 impl App {
-    pub fn new(req: Request, ctx: RouteContext<()>) -> App {
-        App { req, ctx }
+    pub(crate) fn new(req: IncomingRequest, ctx: CommandContext) -> App {
+        App {
+            req,
+            ctx,
+            event: Event::new("interaction"),
+            failure: Failure::default(),
+        }
     }
 
-    fn var(&self, key: &str) -> Result<String, Error> {
-        return match self.ctx.var(key) {
-            Ok(var) => Ok(var.to_string()),
-            Err(_) => Err(Error::EnvironmentVariableNotFound(key.to_string())),
-        };
-    }
-    fn header(&self, key: &str) -> Result<String, Error> {
-        return match self.req.headers().get(key) {
-            Ok(val) => val.ok_or_else(|| Error::HeaderNotFound(key.to_string())),
-            Err(_) => Err(Error::HeaderNotFound(key.to_string())),
-        };
+    fn header(&self, key: &str) -> Option<String> {
+        self.req.headers.get(&key.to_lowercase()).cloned()
     }
 
     async fn validate_sig(&mut self) -> Result<String, Error> {
-        let pubkey = self.var("DISCORD_PUBLIC_KEY")?;
-        let signature = self.header("x-signature-ed25519")?;
-        let timestamp = self.header("x-signature-timestamp")?;
+        let guard = RequestGuard::new(self.ctx.config().max_body_bytes);
+        let headers = guard.check_head(&self.req.method, |key| self.header(key))?;
+
+        // A malformed key is a deployment problem rather than a bad request, so
+        // report it as such instead of failing every signature check
+        let public_keys = cached_public_keys(&self.ctx.config().discord_public_key)
+            .map_err(|err| Error::InvalidConfiguration(format!("DISCORD_PUBLIC_KEY: {}", err)))?;
 
         let body = self
             .req
-            .text()
+            .body
+            .take()
+            .ok_or_else(|| Error::InvalidPayload("body was already read".into()))?
             .await
-            .map_err(|_| Error::InvalidPayload("".into()))?;
-        verify_signature(&pubkey, &signature, &timestamp, &body)
+            .map_err(|err| Error::InvalidPayload(format!("unable to read body: {}", err)))?;
+        let body = guard.check_body(body)?;
+
+        verify_signature(&public_keys, &headers.signature, &headers.timestamp, &body)
             .map_err(Error::VerificationFailed)?;
-        return Ok(body);
+        verify_timestamp(
+            &headers.timestamp,
+            chrono::Utc::now().timestamp(),
+            self.ctx.config().signature_max_age,
+        )
+        .map_err(Error::VerificationFailed)?;
+        Ok(body)
     }
 
+    /// Rejects interactions whose ID has already been seen within the signature window.
+    ///
+    /// KV is eventually consistent, so this catches replays that slip past the
+    /// timestamp check in practice rather than guaranteeing exactly-once delivery.
+    async fn deduplicate(&self, store: &dyn Store, interaction: &Interaction) -> Result<(), Error> {
+        let id = match (self.ctx.config().deduplicate_interactions, interaction.id()) {
+            (true, Some(id)) => id,
+            _ => return Ok(()),
+        };
+
+        let key = format!("seen_interaction:{}", id);
+
+        if store.get(&key).await?.is_some() {
+            return Err(Error::VerificationFailed(
+                VerificationError::ReplayRejected(format!(
+                    "interaction {} was already handled",
+                    id
+                )),
+            ));
+        }
+
+        let ttl = (self.ctx.config().signature_max_age.max(0) as u64).max(MIN_TTL);
+        store.put(&key, "1", Some(ttl)).await?;
+
+        Ok(())
+    }
+
+    /// Answers the interaction, then logs a single event describing how it went.
     pub async fn handle_request(&mut self) -> Result<InteractionResponse, HttpError> {
-        let body = self.validate_sig().await?;
+        let started = chrono::Utc::now();
+        let result = self.respond().await.map_err(HttpError::from);
+        let duration = (chrono::Utc::now() - started).num_milliseconds();
 
-        worker::console_log!("Request body : {}", body);
+        let outcome = match &result {
+            Ok(_) => self.ctx.outcome().unwrap_or(Outcome::Answered),
+            Err(err) if (err.status as u16) < 500 => Outcome::Rejected,
+            Err(_) => Outcome::Failed,
+        };
+        let level = match outcome {
+            Outcome::Failed => Level::Error,
+            Outcome::UpstreamUnavailable => Level::Warn,
+            _ => Level::Info,
+        };
+        std::mem::replace(&mut self.event, Event::new("interaction"))
+            .with("guild_id", self.ctx.guild_id())
+            .with("outcome", outcome)
+            .with("duration_ms", duration)
+            .with("cache", &self.ctx.cache_metrics().borrow().namespaces)
+            .with("upstream", &*self.ctx.upstream_calls().borrow())
+            .emit(level);
+        self.report_failure(outcome, &result);
 
-        let interaction = serde_json::from_str::<Interaction>(&body).map_err(Error::JsonFailed)?;
-        worker::console_log! {"Request parsed : {}", serde_json::to_string_pretty(&interaction).unwrap()};
-        let response = interaction.perform(&mut self.ctx).await?;
+        result
+    }
 
-        Ok(response)
+    fn report_failure(
+        &mut self,
+        outcome: Outcome,
+        result: &Result<InteractionResponse, HttpError>,
+    ) {
+        let (kind, context) = match (outcome, result) {
+            (Outcome::Failed, Err(err)) => (err.kind().to_string(), err.context()),
+            (Outcome::UpstreamUnavailable, _) => match self.ctx.upstream_calls().borrow().last() {
+                Some(call) => (call.kind(), call.error.clone().unwrap_or_default()),
+                None => ("upstream".into(), String::new()),
+            },
+            _ => return,
+        };
+        let failure = Failure {
+            kind,
+            context,
+            guild_id: self.ctx.guild_id().map(String::from),
+            ..std::mem::take(&mut self.failure)
+        };
+        error_report::report(&self.ctx, failure);
+    }
+
+    async fn respond(&mut self) -> Result<InteractionResponse, Error> {
+        let body = self.validate_sig().await?;
+
+        let interaction = serde_json::from_str::<Interaction>(&body).map_err(Error::JsonFailed)?;
+        // The token can post as the bot for 15 minutes, keep it out of every later message
+        logger::add_secret(interaction.token());
+        self.event.insert("interaction_id", interaction.id());
+        self.event.insert("type", interaction.kind());
+        self.event.insert("command", interaction.command_name());
+        self.failure.interaction_id = interaction.id().map(String::from);
+        self.failure.command = interaction.command_name().map(String::from);
+        self.failure.options = interaction.options_summary();
+        log_debug!(
+            "Request parsed : {}",
+            logger::redact_json(&serde_json::to_value(&interaction).map_err(Error::JsonFailed)?)
+        );
+        self.deduplicate(self.ctx.store(), &interaction).await?;
+        interaction.perform(&mut self.ctx).await
     }
 }
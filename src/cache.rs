@@ -0,0 +1,178 @@
+//! A typed read-through cache for upstream responses, kept in the [`Store`].
+//!
+//! Entries are fresh for a while, then stale for a while: stale entries are
+//! still served, but refreshed in the background so the next caller gets a
+//! fresh one. After that they expire from the store entirely.
+
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::future::Future;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::context::CommandContext;
+use crate::store::migration::{migrate, read_record, write_record, MigrationReport, Record};
+use crate::store::{Store, StoreError, MIN_TTL};
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct CachePolicy {
+    /// Seconds an entry is served without refreshing it.
+    pub(crate) fresh_for: u64,
+    /// Seconds after that an entry is still served while being refreshed.
+    pub(crate) stale_for: u64,
+}
+
+impl CachePolicy {
+    pub(crate) fn new(fresh_for: u64, stale_for: u64) -> Self {
+        Self {
+            fresh_for,
+            stale_for,
+        }
+    }
+
+    fn ttl(&self) -> u64 {
+        (self.fresh_for + self.stale_for).max(MIN_TTL)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum CacheStatus {
+    Hit,
+    Stale,
+    Miss,
+}
+
+#[derive(Deserialize, Serialize, Default, Clone, Copy, Debug)]
+pub(crate) struct CacheCounts {
+    pub(crate) hits: u32,
+    pub(crate) stale: u32,
+    pub(crate) misses: u32,
+}
+
+/// Lookups per namespace over the lifetime of a [`CommandContext`].
+#[derive(Serialize, Default, Debug)]
+pub(crate) struct CacheMetrics {
+    pub(crate) namespaces: BTreeMap<String, CacheCounts>,
+}
+
+impl CacheMetrics {
+    fn record(&mut self, namespace: &str, status: CacheStatus) {
+        let counts = self.namespaces.entry(namespace.to_string()).or_default();
+        match status {
+            CacheStatus::Hit => counts.hits += 1,
+            CacheStatus::Stale => counts.stale += 1,
+            CacheStatus::Miss => counts.misses += 1,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(bound(deserialize = "T: DeserializeOwned"))]
+struct CacheEntry<T> {
+    stored_at: i64,
+    value: T,
+}
+
+impl<T: Serialize + DeserializeOwned> Record for CacheEntry<T> {
+    const KIND: &'static str = "cache entry";
+    const PREFIX: &'static str = "cached:";
+    const VERSION: u32 = 1;
+    const DISPOSABLE: bool = true;
+
+    fn upgrade(version: u32, _value: Value) -> Result<Value, StoreError> {
+        Err(StoreError::UnsupportedVersion(Self::KIND.into(), version))
+    }
+}
+
+fn entry_key(namespace: &str, key: &str) -> String {
+    format!("{}{}:{}", CacheEntry::<Value>::PREFIX, namespace, key)
+}
+
+/// Drops entries stored in an older format, see [`migrate`].
+pub(crate) async fn migrate_entries(store: &dyn Store) -> Result<MigrationReport, StoreError> {
+    migrate::<CacheEntry<Value>>(store).await
+}
+
+/// Writes `value` as a new entry, handing it back whether or not that worked.
+async fn store_entry<T: Serialize + DeserializeOwned>(
+    store: &dyn Store,
+    key: &str,
+    value: T,
+    policy: CachePolicy,
+) -> (T, Result<(), StoreError>) {
+    let entry = CacheEntry {
+        stored_at: chrono::Utc::now().timestamp(),
+        value,
+    };
+    let result = write_record(store, key, &entry, Some(policy.ttl())).await;
+    (entry.value, result)
+}
+
+/// Returns the cached value for `key` in `namespace`, calling `fetch` on a miss.
+///
+/// Stale values are returned as they are while `fetch` runs again in the
+/// background. Storage failures are logged and treated as a miss, so the cache
+/// can never make a lookup fail that would otherwise have succeeded.
+pub(crate) async fn cached<T, E, F, Fut>(
+    ctx: &CommandContext,
+    namespace: &'static str,
+    key: &str,
+    policy: CachePolicy,
+    fetch: F,
+) -> Result<T, E>
+where
+    T: Serialize + DeserializeOwned + 'static,
+    E: Display + 'static,
+    F: FnOnce() -> Fut + 'static,
+    Fut: Future<Output = Result<T, E>> + 'static,
+{
+    let key = entry_key(namespace, key);
+    let entry = match read_record::<CacheEntry<T>>(ctx.store(), &key).await {
+        Ok(entry) => entry,
+        Err(err) => {
+            log_warn!("Unable to read cache entry {} : {}", key, err);
+            None
+        }
+    };
+
+    let age = entry
+        .as_ref()
+        .map(|entry| (chrono::Utc::now().timestamp() - entry.stored_at).max(0) as u64);
+    let status = match age {
+        Some(age) if age < policy.fresh_for => CacheStatus::Hit,
+        Some(age) if age < policy.fresh_for + policy.stale_for => CacheStatus::Stale,
+        _ => CacheStatus::Miss,
+    };
+    ctx.cache_metrics().borrow_mut().record(namespace, status);
+    log_debug!("Cache {:?} for {}", status, key);
+
+    match (status, entry) {
+        (CacheStatus::Hit, Some(entry)) => Ok(entry.value),
+        (CacheStatus::Stale, Some(entry)) => {
+            let store = ctx.shared_store();
+            ctx.background().spawn(async move {
+                let result = match fetch().await {
+                    Ok(value) => store_entry(store.as_ref(), &key, value, policy)
+                        .await
+                        .1
+                        .map_err(|err| err.to_string()),
+                    Err(err) => Err(err.to_string()),
+                };
+                if let Err(err) = result {
+                    log_warn!("Unable to refresh cache entry {} : {}", key, err);
+                }
+            });
+            Ok(entry.value)
+        }
+        _ => {
+            let value = fetch().await?;
+            let (value, result) = store_entry(ctx.store(), &key, value, policy).await;
+            if let Err(err) = result {
+                log_warn!("Unable to write cache entry {} : {}", key, err);
+            }
+            Ok(value)
+        }
+    }
+}
@@ -0,0 +1,92 @@
+//! Stops calling an upstream that keeps failing, so interactions answer from
+//! the cache or with a notice instead of waiting on it.
+//!
+//! The state lives in the store, shared by every isolate. Stores like KV are
+//! eventually consistent, so a few calls may still get through after the
+//! circuit opens, which is fine for shedding load off a struggling upstream.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::store::migration::{read_record, write_record, Record};
+use crate::store::{Store, StoreError};
+use crate::upstream::Upstream;
+
+/// Failed calls in a row, after retries, that open the circuit.
+const FAILURE_THRESHOLD: u32 = 5;
+/// Seconds an open circuit stays open before calls are tried again.
+const COOLDOWN: i64 = 60;
+/// Seconds without failures after which earlier ones are forgotten.
+const FAILURE_WINDOW: u64 = 10 * 60;
+
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+pub(crate) struct CircuitState {
+    pub(crate) failures: u32,
+    /// Unix seconds until which calls are not attempted.
+    pub(crate) open_until: Option<i64>,
+}
+
+impl Record for CircuitState {
+    const KIND: &'static str = "circuit state";
+    const PREFIX: &'static str = "circuit:";
+    const VERSION: u32 = 1;
+    const TTL: Option<u64> = Some(if FAILURE_WINDOW > COOLDOWN as u64 {
+        FAILURE_WINDOW
+    } else {
+        COOLDOWN as u64
+    });
+
+    fn upgrade(version: u32, _value: Value) -> Result<Value, StoreError> {
+        Err(StoreError::UnsupportedVersion(Self::KIND.into(), version))
+    }
+}
+
+fn circuit_key(upstream: Upstream) -> String {
+    format!("{}{}", CircuitState::PREFIX, upstream)
+}
+
+impl CircuitState {
+    /// The state of `upstream`'s circuit, closed when none is stored.
+    pub(crate) async fn load(store: &dyn Store, upstream: Upstream) -> Result<Self, StoreError> {
+        Ok(read_record(store, &circuit_key(upstream))
+            .await?
+            .unwrap_or_default())
+    }
+
+    pub(crate) fn is_open(&self, now: i64) -> bool {
+        self.open_until.is_some_and(|open_until| now < open_until)
+    }
+
+    /// Closes the circuit again, writing only when there was anything to forget.
+    pub(crate) async fn record_success(
+        &self,
+        store: &dyn Store,
+        upstream: Upstream,
+    ) -> Result<(), StoreError> {
+        if self.failures == 0 && self.open_until.is_none() {
+            return Ok(());
+        }
+        store.delete(&circuit_key(upstream)).await
+    }
+
+    /// Counts a failure, opening the circuit once they reach the threshold.
+    pub(crate) async fn record_failure(
+        mut self,
+        store: &dyn Store,
+        upstream: Upstream,
+        now: i64,
+    ) -> Result<Self, StoreError> {
+        self.failures += 1;
+        if self.failures >= FAILURE_THRESHOLD {
+            log_warn!(
+                "Opening the circuit for {} for {}s after {} failures",
+                upstream,
+                COOLDOWN,
+                self.failures
+            );
+            self.open_until = Some(now + COOLDOWN);
+        }
+        write_record(store, &circuit_key(upstream), &self, Self::TTL).await?;
+        Ok(self)
+    }
+}
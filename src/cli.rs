@@ -0,0 +1,397 @@
+//! Runs commands from the terminal against a local store, without Discord.
+//!
+//! `nuko-cli xe --from AUD --to JPY --amount 50` runs a single command and
+//! prints its response, without a command it reads one command per line.
+//! Commands go through the same handlers as interactions, so guild settings,
+//! usage counters and caches behave as they do when deployed.
+
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use serde_json::json;
+use tokio::task::LocalSet;
+
+use crate::command::init_commands;
+use crate::config::Config;
+use crate::context::{Background, CommandContext};
+use crate::embed::Embed;
+use crate::error::Error;
+use crate::guild_config::ADMINISTRATOR;
+use crate::interaction::{
+    ApplicationCommandOptionType, Interaction, InteractionApplicationCommandCallbackData,
+    InteractionResponse,
+};
+use crate::native::{var, DEFAULT_DATA_FILE};
+use crate::store::{FileStore, Store};
+
+const USAGE: &str = "\
+Usage: nuko-cli [--json] [--data FILE] [--user ID] [--guild ID] [--permissions BITS] [LINE]
+
+A line is `COMMAND [--OPTION VALUE]...`, for example `xe --from AUD --to JPY --amount 50`.
+Put `complete` in front of it to run the autocomplete for the last option instead.
+Without a line, lines are read from a prompt, where `commands`, `json`, `help`
+and `quit` are also understood.";
+
+#[derive(Debug, thiserror::Error)]
+enum CliError {
+    #[error("Unknown command '{0}', `commands` lists them.")]
+    UnknownCommand(String),
+
+    #[error("/{0} has no option '{1}'.")]
+    UnknownOption(String, String),
+
+    #[error("Expected an option, found '{0}'.")]
+    UnexpectedArgument(String),
+
+    #[error("Missing a value for '{0}'.")]
+    MissingValue(String),
+
+    #[error("Missing the required option '--{0}'.")]
+    MissingOption(String),
+
+    #[error("Unterminated quote.")]
+    UnterminatedQuote,
+
+    #[error("{0}")]
+    Failed(String),
+}
+
+impl From<Error> for CliError {
+    fn from(error: Error) -> Self {
+        // The wrapped error says what went wrong, the wrapper only that something did
+        CliError::Failed(match error {
+            Error::InteractionFailed(inner) => inner.to_string(),
+            error => error.to_string(),
+        })
+    }
+}
+
+/// Who a command runs as and how its response is printed.
+#[derive(Clone)]
+struct Session {
+    json: bool,
+    user_id: String,
+    guild_id: Option<String>,
+    permissions: String,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self {
+            json: false,
+            user_id: "0".into(),
+            guild_id: None,
+            permissions: ADMINISTRATOR.to_string(),
+        }
+    }
+}
+
+/// Runs the line given as arguments, or a prompt when there is none.
+pub fn run() -> std::io::Result<()> {
+    // Plain lines read better next to command output
+    let config = Config::without_discord(|key| match key {
+        "LOG_FORMAT" => var(key).or_else(|| Some("text".into())),
+        _ => var(key),
+    })
+    .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, err.to_string()))?;
+    crate::init_logger(Some(&config));
+    let config = Rc::new(config);
+
+    let mut args = std::env::args().skip(1).collect::<Vec<String>>();
+    let mut session = Session::default();
+    let mut data_file =
+        PathBuf::from(var("NUKO_DATA_FILE").unwrap_or_else(|| DEFAULT_DATA_FILE.into()));
+    while let Some(flag) = args.first().filter(|arg| arg.starts_with("--")).cloned() {
+        args.remove(0);
+        let mut value = || {
+            if args.is_empty() {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    CliError::MissingValue(flag.clone()).to_string(),
+                ));
+            }
+            Ok(args.remove(0))
+        };
+        match flag.as_str() {
+            "--json" => session.json = true,
+            "--data" => data_file = PathBuf::from(value()?),
+            "--user" => session.user_id = value()?,
+            "--guild" => session.guild_id = Some(value()?),
+            "--permissions" => session.permissions = value()?,
+            _ => {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+        }
+    }
+
+    let store: Rc<dyn Store> =
+        Rc::new(FileStore::open(data_file).map_err(|err| std::io::Error::other(err.to_string()))?);
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+
+    LocalSet::new().block_on(&runtime, async move {
+        if args.is_empty() {
+            repl(store, config, session).await
+        } else {
+            let words = args.iter().map(String::as_str).collect::<Vec<&str>>();
+            if let Err(err) = execute(&words, store, config, &session).await {
+                eprintln!("{}", err);
+            }
+            Ok(())
+        }
+    })
+}
+
+async fn repl(
+    store: Rc<dyn Store>,
+    config: Rc<Config>,
+    mut session: Session,
+) -> std::io::Result<()> {
+    let stdin = std::io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        print!("nuko> ");
+        std::io::stdout().flush()?;
+        let line = match lines.next() {
+            Some(line) => line?,
+            None => return Ok(()),
+        };
+
+        let words = match split(&line) {
+            Ok(words) => words,
+            Err(err) => {
+                eprintln!("{}", err);
+                continue;
+            }
+        };
+        let words = words.iter().map(String::as_str).collect::<Vec<&str>>();
+        match words.as_slice() {
+            [] => {}
+            ["quit"] | ["exit"] => return Ok(()),
+            ["help"] => println!("{}", USAGE),
+            ["commands"] => init_commands()
+                .iter()
+                .for_each(|com| println!("{:<8} {}", com.name(), com.description())),
+            ["json"] => {
+                session.json = !session.json;
+                println!("JSON output {}", if session.json { "on" } else { "off" });
+            }
+            words => {
+                if let Err(err) = execute(words, store.clone(), config.clone(), &session).await {
+                    eprintln!("{}", err);
+                }
+            }
+        }
+    }
+}
+
+/// Splits a line into words like a shell would, honouring quotes and escapes.
+fn split(line: &str) -> Result<Vec<String>, CliError> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('"'), '\\') | (None, '\\') => {
+                if let Some(escaped) = chars.next() {
+                    word.get_or_insert_with(String::new).push(escaped);
+                }
+            }
+            (Some(_), c) => word.get_or_insert_with(String::new).push(c),
+            (None, '"') | (None, '\'') => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (None, c) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err(CliError::UnterminatedQuote);
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+/// Builds the interaction Discord would send for `words`.
+fn interaction(words: &[&str], session: &Session) -> Result<Interaction, CliError> {
+    let (autocomplete, words) = match words {
+        ["complete", words @ ..] => (true, words),
+        words => (false, words),
+    };
+    let (name, mut rest) = match words {
+        [name, rest @ ..] => (*name, rest),
+        [] => return Err(CliError::MissingValue("complete".into())),
+    };
+    let command = init_commands()
+        .into_iter()
+        .find(|com| com.name() == name)
+        .ok_or_else(|| CliError::UnknownCommand(name.into()))?;
+    let declared = command.options().unwrap_or_default();
+
+    let mut options = Vec::new();
+    while let [word, tail @ ..] = rest {
+        let (key, inline) = match word.strip_prefix("--") {
+            Some(option) => match option.split_once('=') {
+                Some((key, value)) => (key, Some(value)),
+                None => (option, None),
+            },
+            None => return Err(CliError::UnexpectedArgument((*word).into())),
+        };
+        let option = declared
+            .iter()
+            .find(|option| option.name == key)
+            .ok_or_else(|| CliError::UnknownOption(name.into(), key.into()))?;
+
+        rest = tail;
+        let value = match (inline, &option.ty, rest) {
+            (Some(value), _, _) => value.to_string(),
+            (None, ApplicationCommandOptionType::Boolean, _) => "true".into(),
+            (None, _, [value, tail @ ..]) if !value.starts_with("--") => {
+                rest = tail;
+                value.to_string()
+            }
+            // The option being completed may not have a value yet
+            (None, _, []) if autocomplete => String::new(),
+            _ => return Err(CliError::MissingValue(format!("--{}", key))),
+        };
+        options.push(json!({ "name": key, "type": option.ty, "value": value }));
+    }
+
+    if autocomplete {
+        if let Some(last) = options.last_mut() {
+            last["focused"] = json!(true);
+        }
+    } else if let Some(missing) = declared.iter().find(|option| {
+        option.required == Some(true) && !options.iter().any(|given| given["name"] == option.name)
+    }) {
+        return Err(CliError::MissingOption(missing.name.clone()));
+    }
+
+    let user = json!({
+        "avatar": "",
+        "discriminator": "0",
+        "id": session.user_id,
+        "public_flags": 0,
+        "username": "nuko-cli",
+    });
+    let (member, user) = match &session.guild_id {
+        Some(_) => (
+            json!({
+                "user": user,
+                "roles": [],
+                "premium_since": null,
+                "permissions": session.permissions,
+                "pending": false,
+                "nick": null,
+                "mute": false,
+                "joined_at": chrono::Utc::now().to_rfc3339(),
+                "is_pending": false,
+                "deaf": false,
+            }),
+            json!(null),
+        ),
+        None => (json!(null), user),
+    };
+
+    serde_json::from_value(json!({
+        "id": "0",
+        "type": if autocomplete { 4 } else { 2 },
+        "token": "nuko-cli",
+        "guild_id": session.guild_id,
+        "member": member,
+        "user": user,
+        "data": { "name": name, "options": options },
+    }))
+    .map_err(|err| CliError::Failed(err.to_string()))
+}
+
+async fn execute(
+    words: &[&str],
+    store: Rc<dyn Store>,
+    config: Rc<Config>,
+    session: &Session,
+) -> Result<(), CliError> {
+    let interaction = interaction(words, session)?;
+    let background = Background::default();
+    let mut ctx = CommandContext::new(store, config, background.clone());
+
+    let result = interaction.perform(&mut ctx).await;
+    // Unlike a server there is nothing to answer first, so let cache refreshes
+    // and usage counters finish before the next line
+    background.run().await;
+    let response = result?;
+
+    if session.json {
+        let json = serde_json::to_string_pretty(&response)
+            .map_err(|err| CliError::Failed(err.to_string()))?;
+        println!("{}", json);
+    } else {
+        print!("{}", render(&response));
+    }
+
+    for file in response.files {
+        std::fs::write(&file.filename, &file.content)
+            .map_err(|err| CliError::Failed(err.to_string()))?;
+        println!("Saved {} ({} bytes)", file.filename, file.content.len());
+    }
+    Ok(())
+}
+
+/// A response as plain text, roughly laid out the way Discord shows it.
+fn render(response: &InteractionResponse) -> String {
+    let data = match &response.data {
+        Some(data) => data,
+        None => return "(no content)\n".into(),
+    };
+    let InteractionApplicationCommandCallbackData {
+        content,
+        choices,
+        embeds,
+        ..
+    } = data;
+
+    let mut out = String::new();
+    if let Some(content) = content {
+        out.push_str(&format!("{}\n", content));
+    }
+    for embed in embeds.iter().flatten() {
+        out.push_str(&render_embed(embed));
+    }
+    for choice in choices.iter().flatten() {
+        if choice.name == choice.value {
+            out.push_str(&format!("- {}\n", choice.name));
+        } else {
+            out.push_str(&format!("- {} ({})\n", choice.name, choice.value));
+        }
+    }
+    if out.is_empty() {
+        out.push_str("(no content)\n");
+    }
+    out
+}
+
+fn render_embed(embed: &Embed) -> String {
+    let mut out = format!("== {} ==\n", embed.title);
+    if let Some(url) = &embed.url {
+        out.push_str(&format!("{}\n", url));
+    }
+    if !embed.description.is_empty() {
+        out.push_str(&format!("{}\n", embed.description));
+    }
+    for field in &embed.fields {
+        out.push_str(&format!("\n{}\n", field.name));
+        for line in field.value.lines() {
+            out.push_str(&format!("  {}\n", line));
+        }
+    }
+    if let Some(footer) = &embed.footer {
+        out.push_str(&format!("\n-- {}\n", footer.text));
+    }
+    out
+}
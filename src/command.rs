@@ -1,8 +1,21 @@
 use crate::commands;
+use crate::config::Config;
+use crate::context::CommandContext;
+use crate::discord::{ApplicationCommand, DiscordClient, DiscordError};
 use crate::error::InteractionError;
+use crate::guild_config::{can_disable, GuildConfig};
 use crate::interaction::*;
+use crate::store::migration::{read_record, write_record, Record};
+use crate::store::{Store, StoreError};
+use crate::upstream::Upstream;
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+/// How many guilds get their commands per registration, each one is a
+/// subrequest, so the rest are left to the next run.
+pub(crate) const GUILD_BATCH_SIZE: usize = 25;
 
 #[async_trait(?Send)]
 pub(crate) trait Command {
@@ -10,7 +23,7 @@ pub(crate) trait Command {
         &self,
         _member: &Option<Member>,
         _options: &Option<Vec<ApplicationCommandInteractionDataOption>>,
-        _ctx: &mut worker::RouteContext<()>,
+        _ctx: &mut CommandContext,
     ) -> Result<InteractionApplicationCommandCallbackData, InteractionError> {
         // Implement the command logic here
         unimplemented!()
@@ -30,10 +43,15 @@ pub(crate) trait Command {
         unimplemented!()
     }
 
+    fn default_member_permissions(&self) -> Option<String> {
+        // Permission bitfield members need to see the command, everyone if `None`
+        None
+    }
+
     async fn autocomplete(
         &self,
         _options: &Option<Vec<ApplicationCommandInteractionDataOption>>,
-        _ctx: &mut worker::RouteContext<()>,
+        _ctx: &mut CommandContext,
     ) -> Result<InteractionApplicationCommandCallbackData, InteractionError> {
         // If your command supports autocomplete implement the logic here
         unimplemented!()
@@ -45,12 +63,222 @@ pub(crate) struct RegisteredCommand {
     pub(crate) name: String,
     pub(crate) description: String,
     pub(crate) options: Option<Vec<ApplicationCommandOption>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) default_member_permissions: Option<String>,
+}
+
+impl RegisteredCommand {
+    /// A SHA-256 of the definition sent to Discord, to tell whether a deployment
+    /// registered the commands it runs.
+    pub(crate) fn hash(&self) -> String {
+        let definition = serde_json::to_vec(self).unwrap_or_default();
+        hex::encode(Sha256::digest(&definition))
+    }
 }
 
 pub(crate) fn init_commands() -> Vec<Box<dyn Command + Sync>> {
     let mut v: Vec<Box<dyn Command + Sync>> = Vec::new();
+    v.push(Box::new(commands::audit::Audit {}));
+    v.push(Box::new(commands::config::Config {}));
     v.push(Box::new(commands::hey::Hey {}));
     v.push(Box::new(commands::jisho::Jisho {}));
+    v.push(Box::new(commands::mydata::MyData {}));
+    v.push(Box::new(commands::stats::Stats {}));
     v.push(Box::new(commands::xe::XE {}));
     v
 }
+
+/// Every command in [`init_commands`], as registered with Discord.
+pub(crate) fn registered_commands() -> Vec<RegisteredCommand> {
+    init_commands()
+        .iter()
+        .map(|com| RegisteredCommand {
+            name: com.name(),
+            description: com.description(),
+            options: com.options(),
+            default_member_permissions: com.default_member_permissions(),
+        })
+        .collect()
+}
+
+/// The commands to register in a guild, when commands are registered per guild.
+/// Commands that cannot be disabled stay global.
+pub(crate) fn guild_commands(guild: &GuildConfig) -> Vec<RegisteredCommand> {
+    registered_commands()
+        .into_iter()
+        .filter(|command| can_disable(&command.name) && guild.is_enabled(&command.name))
+        .collect()
+}
+
+fn discord_client(config: &Config) -> DiscordClient {
+    DiscordClient::new(Some(config.discord_token.clone()))
+        .with_base_url(config.base_url(Upstream::Discord))
+}
+
+fn upstream_error(err: DiscordError) -> InteractionError {
+    InteractionError::UpstreamError(format!("Discord: {}", err))
+}
+
+/// Overwrites the bot's global commands with every command in [`init_commands`].
+///
+/// With `GUILD_COMMANDS` set, only the commands that cannot be disabled are
+/// global, and the guilds the bot is in get the commands they have enabled,
+/// [`GUILD_BATCH_SIZE`] guilds per call.
+pub(crate) async fn register_commands(
+    ctx: &CommandContext,
+) -> Result<Vec<ApplicationCommand>, InteractionError> {
+    let config = ctx.config();
+    let to_register = registered_commands()
+        .into_iter()
+        .filter(|command| !config.guild_commands || !can_disable(&command.name))
+        .collect::<Vec<RegisteredCommand>>();
+
+    let app_id = &config.discord_application_id;
+    let client = discord_client(config);
+
+    log_info!(
+        "Registering commands : {}",
+        to_register
+            .iter()
+            .map(|command| command.name.as_str())
+            .collect::<Vec<&str>>()
+            .join(", ")
+    );
+
+    let mut registered = client
+        .bulk_overwrite_global_commands(app_id, &to_register)
+        .await
+        .map_err(upstream_error)?;
+    log_info!("Registered {} commands", registered.len());
+
+    registered.extend(sync_guild_batch(ctx, &client).await?);
+
+    Ok(registered)
+}
+
+/// How far registering guild commands got, kept under `guild_commands:sync`.
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+pub(crate) struct GuildCommandSync {
+    /// Whether guilds are being given their commands, or having them cleared
+    /// after `GUILD_COMMANDS` was turned off.
+    pub(crate) enabled: bool,
+    /// Whether a pass over the guilds is still under way.
+    pub(crate) pending: bool,
+    /// The last guild of the previous batch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) after: Option<String>,
+}
+
+impl Record for GuildCommandSync {
+    const KIND: &'static str = "guild command sync";
+    const PREFIX: &'static str = "guild_commands:";
+    const VERSION: u32 = 1;
+
+    fn upgrade(version: u32, _value: Value) -> Result<Value, StoreError> {
+        Err(StoreError::UnsupportedVersion(Self::KIND.into(), version))
+    }
+}
+
+const GUILD_COMMAND_SYNC_KEY: &str = "guild_commands:sync";
+
+impl GuildCommandSync {
+    pub(crate) async fn load(store: &dyn Store) -> Result<Self, StoreError> {
+        Ok(read_record(store, GUILD_COMMAND_SYNC_KEY)
+            .await?
+            .unwrap_or_default())
+    }
+
+    async fn save(&self, store: &dyn Store) -> Result<(), StoreError> {
+        write_record(store, GUILD_COMMAND_SYNC_KEY, self, None).await
+    }
+}
+
+/// Overwrites the commands of the next [`GUILD_BATCH_SIZE`] guilds, with the
+/// ones each has enabled, or with none once `GUILD_COMMANDS` is turned off.
+///
+/// A pass over every guild starts on each call while `GUILD_COMMANDS` is set,
+/// unless one is still under way, and once when it is turned off.
+async fn sync_guild_batch(
+    ctx: &CommandContext,
+    client: &DiscordClient,
+) -> Result<Vec<ApplicationCommand>, InteractionError> {
+    let config = ctx.config();
+    let mut sync = GuildCommandSync::load(ctx.store()).await?;
+    if sync.enabled != config.guild_commands || (sync.enabled && !sync.pending) {
+        sync = GuildCommandSync {
+            enabled: config.guild_commands,
+            pending: true,
+            after: None,
+        };
+    }
+    if !sync.pending {
+        return Ok(Vec::new());
+    }
+
+    let app_id = &config.discord_application_id;
+    let batch = client
+        .get_current_user_guilds(sync.after.as_deref(), GUILD_BATCH_SIZE)
+        .await
+        .map_err(upstream_error)?;
+    let mut registered = Vec::new();
+    for guild in &batch {
+        let commands = if sync.enabled {
+            guild_commands(&GuildConfig::load(ctx.store(), &guild.id).await?)
+        } else {
+            Vec::new()
+        };
+        registered.extend(
+            client
+                .bulk_overwrite_guild_commands(app_id, &guild.id, &commands)
+                .await
+                .map_err(upstream_error)?,
+        );
+    }
+
+    // A failed batch is not saved, so it is tried again as a whole
+    sync.pending = batch.len() == GUILD_BATCH_SIZE;
+    sync.after = if sync.pending {
+        batch.last().map(|guild| guild.id.clone())
+    } else {
+        None
+    };
+    sync.save(ctx.store()).await?;
+    log_info!(
+        "{} the commands of {} guilds{}",
+        if sync.enabled {
+            "Registered"
+        } else {
+            "Cleared"
+        },
+        batch.len(),
+        if sync.pending {
+            ", the rest are left to the next run"
+        } else {
+            ""
+        }
+    );
+
+    Ok(registered)
+}
+
+/// Overwrites a guild's commands with the ones it has enabled, in the
+/// background, when commands are registered per guild.
+pub(crate) fn sync_guild_commands(ctx: &CommandContext, guild_id: &str, guild: &GuildConfig) {
+    let config = ctx.config();
+    if !config.guild_commands {
+        return;
+    }
+
+    let client = discord_client(config);
+    let app_id = config.discord_application_id.clone();
+    let guild_id = guild_id.to_string();
+    let commands = guild_commands(guild);
+    ctx.background().spawn(async move {
+        if let Err(err) = client
+            .bulk_overwrite_guild_commands(&app_id, &guild_id, &commands)
+            .await
+        {
+            log_warn!("Unable to update the commands of a guild : {}", err);
+        }
+    });
+}
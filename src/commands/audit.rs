@@ -0,0 +1,98 @@
+use crate::audit::{self, guild_log_prefix};
+use crate::command::Command;
+use crate::context::CommandContext;
+use crate::embed::{Embed, DEFAULT_COLOR};
+use crate::error::InteractionError;
+use crate::guild_config::{has_permission, ADMINISTRATOR};
+use crate::interaction::{
+    ApplicationCommandInteractionDataOption, ApplicationCommandOption,
+    InteractionApplicationCommandCallbackData, Member, EPHEMERAL,
+};
+use crate::locale::Text;
+
+use async_trait::async_trait;
+
+/// Embeds hold at most 25 fields.
+const ENTRIES: usize = 10;
+
+pub(crate) struct Audit {}
+
+impl Audit {
+    fn message(content: &str) -> InteractionApplicationCommandCallbackData {
+        InteractionApplicationCommandCallbackData {
+            content: Some(content.into()),
+            choices: None,
+            embeds: None,
+            flags: Some(EPHEMERAL),
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl Command for Audit {
+    async fn respond(
+        &self,
+        member: &Option<Member>,
+        _options: &Option<Vec<ApplicationCommandInteractionDataOption>>,
+        ctx: &mut CommandContext,
+    ) -> Result<InteractionApplicationCommandCallbackData, InteractionError> {
+        let language = ctx.language();
+        let (member, guild_id) = match (member, ctx.guild_id()) {
+            (Some(member), Some(guild_id)) => (member, guild_id),
+            _ => return Ok(Audit::message(language.text(Text::GuildOnly))),
+        };
+        if !has_permission(&member.permissions, ADMINISTRATOR) {
+            return Ok(Audit::message(language.text(Text::AdministratorRequired)));
+        }
+
+        let entries = audit::recent(ctx.store(), &guild_log_prefix(guild_id), ENTRIES).await?;
+
+        Ok(InteractionApplicationCommandCallbackData {
+            content: None,
+            choices: None,
+            embeds: Some(vec![Embed {
+                title: language.text(Text::AuditTitle).into(),
+                description: if entries.is_empty() {
+                    language.text(Text::NoAuditEntries).into()
+                } else {
+                    language.text(Text::AuditHelp).into()
+                },
+                fields: entries.iter().map(audit::describe).collect(),
+                thumbnail: None,
+                color: Some(DEFAULT_COLOR),
+                url: None,
+                footer: None,
+            }]),
+            flags: Some(EPHEMERAL),
+        })
+    }
+
+    fn name(&self) -> String {
+        "audit".into()
+    }
+
+    fn description(&self) -> String {
+        "Show the latest configuration changes in this server".into()
+    }
+
+    fn options(&self) -> Option<Vec<ApplicationCommandOption>> {
+        None
+    }
+
+    fn default_member_permissions(&self) -> Option<String> {
+        Some(ADMINISTRATOR.to_string())
+    }
+
+    async fn autocomplete(
+        &self,
+        _options: &Option<Vec<ApplicationCommandInteractionDataOption>>,
+        _ctx: &mut CommandContext,
+    ) -> Result<InteractionApplicationCommandCallbackData, InteractionError> {
+        Ok(InteractionApplicationCommandCallbackData {
+            content: None,
+            embeds: None,
+            choices: None,
+            flags: None,
+        })
+    }
+}
@@ -0,0 +1,269 @@
+use crate::audit;
+use crate::command::{init_commands, sync_guild_commands, Command};
+use crate::commands::xe::CurrencyCodes;
+use crate::context::CommandContext;
+use crate::embed::{Embed, EmbedField, DEFAULT_COLOR};
+use crate::error::InteractionError;
+use crate::guild_config::{can_disable, has_permission, GuildConfig, MANAGE_GUILD};
+use crate::interaction::{
+    ApplicationCommandInteractionDataOption, ApplicationCommandOption,
+    ApplicationCommandOptionChoice, ApplicationCommandOptionType,
+    InteractionApplicationCommandCallbackData, Member, EPHEMERAL,
+};
+use crate::locale::{Language, Text};
+
+use async_trait::async_trait;
+use serde_json::Value;
+use strum::IntoEnumIterator;
+
+const SETTINGS: [&str; 7] = [
+    "currency_from",
+    "currency_to",
+    "precision",
+    "embed_color",
+    "language",
+    "disable_command",
+    "enable_command",
+];
+
+pub(crate) struct Config {}
+
+impl Config {
+    fn message(content: String) -> InteractionApplicationCommandCallbackData {
+        InteractionApplicationCommandCallbackData {
+            content: Some(content),
+            choices: None,
+            embeds: None,
+            flags: Some(EPHEMERAL),
+        }
+    }
+
+    /// Applies `value` to `setting`, an empty value clears it. Returns `false` if
+    /// the value is not valid for the setting.
+    fn apply(config: &mut GuildConfig, setting: &str, value: Option<&str>) -> bool {
+        let is_currency = |code: &str| CurrencyCodes::iter().any(|c| c.to_string() == code);
+        let is_command = |name: &str| init_commands().iter().any(|c| c.name() == name);
+
+        match (setting, value) {
+            ("currency_from", None) => config.currency_from = None,
+            ("currency_to", None) => config.currency_to = None,
+            ("precision", None) => config.precision = None,
+            ("embed_color", None) => config.embed_color = None,
+            ("language", None) => config.language = None,
+            ("currency_from", Some(code)) if is_currency(code) => {
+                config.currency_from = Some(code.into())
+            }
+            ("currency_to", Some(code)) if is_currency(code) => {
+                config.currency_to = Some(code.into())
+            }
+            ("precision", Some(precision)) => match precision.parse::<usize>() {
+                Ok(precision) if precision <= 12 => config.precision = Some(precision),
+                _ => return false,
+            },
+            ("embed_color", Some(color)) => {
+                match u32::from_str_radix(color.trim_start_matches('#'), 16) {
+                    Ok(color) if color <= 0xFFFFFF => config.embed_color = Some(color),
+                    _ => return false,
+                }
+            }
+            ("language", Some(language)) => match language.parse::<Language>() {
+                Ok(language) => config.language = Some(language),
+                Err(_) => return false,
+            },
+            ("disable_command", Some(name)) if is_command(name) && can_disable(name) => {
+                if config.is_enabled(name) {
+                    config.disabled_commands.push(name.into());
+                }
+            }
+            ("enable_command", Some(name)) if is_command(name) => {
+                config.disabled_commands.retain(|n| n != name)
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// The stored value a setting changes, for the audit log.
+    fn value_of(config: &GuildConfig, setting: &str) -> Value {
+        let field = match setting {
+            "disable_command" | "enable_command" => "disabled_commands",
+            setting => setting,
+        };
+        serde_json::to_value(config)
+            .ok()
+            .and_then(|config| config.get(field).cloned())
+            .unwrap_or(Value::Null)
+    }
+
+    fn construct_embed(config: &GuildConfig, language: Language) -> Embed {
+        let not_set = || language.text(Text::NotSet).to_string();
+        let field = |name: &str, value: Option<String>| EmbedField {
+            name: name.into(),
+            value: value.unwrap_or_else(not_set),
+            inline: Some(true),
+        };
+
+        Embed {
+            title: language.text(Text::ConfigTitle).into(),
+            description: language.text(Text::ConfigHelp).into(),
+            fields: vec![
+                field("currency_from", config.currency_from.clone()),
+                field("currency_to", config.currency_to.clone()),
+                field("precision", config.precision.map(|p| p.to_string())),
+                field(
+                    "embed_color",
+                    config.embed_color.map(|c| format!("#{:06x}", c)),
+                ),
+                field("language", config.language.map(|l| l.to_string())),
+                field(
+                    "disabled_commands",
+                    Some(config.disabled_commands.join(", ")).filter(|c| !c.is_empty()),
+                ),
+            ],
+            thumbnail: None,
+            color: Some(DEFAULT_COLOR),
+            url: None,
+            footer: None,
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl Command for Config {
+    async fn respond(
+        &self,
+        member: &Option<Member>,
+        options: &Option<Vec<ApplicationCommandInteractionDataOption>>,
+        ctx: &mut CommandContext,
+    ) -> Result<InteractionApplicationCommandCallbackData, InteractionError> {
+        let language = ctx.language();
+        let (member, guild_id) = match (member, ctx.guild_id()) {
+            (Some(member), Some(guild_id)) => (member, guild_id.to_string()),
+            _ => return Ok(Config::message(language.text(Text::GuildOnly).into())),
+        };
+        // Registration already hides the command, but that can be overridden per guild
+        if !has_permission(&member.permissions, MANAGE_GUILD) {
+            return Ok(Config::message(
+                language.text(Text::ManageGuildRequired).into(),
+            ));
+        }
+
+        let option = |name: &str| {
+            options
+                .iter()
+                .flatten()
+                .find(|option| option.name == name)
+                .and_then(|option| option.value.as_deref())
+                .filter(|value| !value.is_empty())
+        };
+
+        let mut config = ctx.guild_config().clone();
+        let setting = match option("setting") {
+            Some(setting) => setting,
+            None => {
+                return Ok(InteractionApplicationCommandCallbackData {
+                    content: None,
+                    choices: None,
+                    embeds: Some(vec![Config::construct_embed(&config, language)]),
+                    flags: None,
+                })
+            }
+        };
+
+        let value = option("value");
+        let old_value = Config::value_of(&config, setting);
+        if !Config::apply(&mut config, setting, value) {
+            return Ok(Config::message(format!(
+                "{} : {} = {}",
+                language.text(Text::InvalidValue),
+                setting,
+                value.unwrap_or_default()
+            )));
+        }
+        let new_value = Config::value_of(&config, setting);
+        // Nothing to store or log when the setting already had that value
+        if new_value != old_value {
+            config.save(ctx.store(), &guild_id).await?;
+            audit::record(
+                ctx,
+                Some(&guild_id),
+                "config",
+                setting,
+                old_value,
+                new_value,
+            )
+            .await?;
+            log_info!("Updated guild configuration : {}", setting);
+            if setting == "disable_command" || setting == "enable_command" {
+                sync_guild_commands(ctx, &guild_id, &config);
+            }
+        }
+
+        // Answer in the language that was just chosen
+        let language = config.language.unwrap_or_default();
+        let mut embed = Config::construct_embed(&config, language);
+        embed.description = language.text(Text::ConfigUpdated).into();
+        ctx.set_guild(guild_id, config);
+
+        Ok(InteractionApplicationCommandCallbackData {
+            content: None,
+            choices: None,
+            embeds: Some(vec![embed]),
+            flags: None,
+        })
+    }
+
+    fn name(&self) -> String {
+        "config".into()
+    }
+
+    fn description(&self) -> String {
+        "Show or change the settings of this server".into()
+    }
+
+    fn options(&self) -> Option<Vec<ApplicationCommandOption>> {
+        Some(vec![
+            ApplicationCommandOption {
+                name: "setting".into(),
+                autocomplete: Some(false),
+                description: "The setting to change, leave empty to show all settings".into(),
+                required: Some(false),
+                ty: ApplicationCommandOptionType::String,
+                choices: Some(
+                    SETTINGS
+                        .iter()
+                        .map(|setting| ApplicationCommandOptionChoice {
+                            name: setting.to_string(),
+                            value: setting.to_string(),
+                        })
+                        .collect(),
+                ),
+            },
+            ApplicationCommandOption {
+                name: "value".into(),
+                autocomplete: Some(false),
+                description: "The new value, leave empty to clear the setting".into(),
+                required: Some(false),
+                ty: ApplicationCommandOptionType::String,
+                choices: None,
+            },
+        ])
+    }
+
+    fn default_member_permissions(&self) -> Option<String> {
+        Some(MANAGE_GUILD.to_string())
+    }
+
+    async fn autocomplete(
+        &self,
+        _options: &Option<Vec<ApplicationCommandInteractionDataOption>>,
+        _ctx: &mut CommandContext,
+    ) -> Result<InteractionApplicationCommandCallbackData, InteractionError> {
+        Ok(InteractionApplicationCommandCallbackData {
+            content: None,
+            embeds: None,
+            choices: None,
+            flags: None,
+        })
+    }
+}
@@ -1,4 +1,5 @@
 use crate::command::Command;
+use crate::context::CommandContext;
 use crate::error::InteractionError;
 use crate::interaction::{
     ApplicationCommandInteractionDataOption, ApplicationCommandOption,
@@ -16,7 +17,7 @@ impl Command for Hey {
         &self,
         _member: &Option<Member>,
         options: &Option<Vec<ApplicationCommandInteractionDataOption>>,
-        _ctx: &mut worker::RouteContext<()>,
+        _ctx: &mut CommandContext,
     ) -> Result<InteractionApplicationCommandCallbackData, InteractionError> {
         let name = options
             .as_ref()
@@ -37,6 +38,7 @@ impl Command for Hey {
             content: Some(format!("Hey, {}!", name)),
             choices: None,
             embeds: None,
+            flags: None,
         })
     }
 
@@ -62,7 +64,7 @@ impl Command for Hey {
     async fn autocomplete(
         &self,
         _options: &Option<Vec<ApplicationCommandInteractionDataOption>>,
-        _ctx: &mut worker::RouteContext<()>,
+        _ctx: &mut CommandContext,
     ) -> Result<InteractionApplicationCommandCallbackData, InteractionError> {
         Ok(InteractionApplicationCommandCallbackData {
             content: None,
@@ -81,6 +83,7 @@ impl Command for Hey {
                     value: "Yoroshi".into(),
                 },
             ]),
+            flags: None,
         })
     }
 }
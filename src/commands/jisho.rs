@@ -1,4 +1,5 @@
 use crate::command::Command;
+use crate::context::CommandContext;
 use crate::error::InteractionError;
 use crate::helpers::jisho_client::JishoClient;
 use crate::interaction::{
@@ -16,7 +17,7 @@ impl Command for Jisho {
         &self,
         _member: &Option<Member>,
         options: &Option<Vec<ApplicationCommandInteractionDataOption>>,
-        _ctx: &mut worker::RouteContext<()>,
+        ctx: &mut CommandContext,
     ) -> Result<InteractionApplicationCommandCallbackData, InteractionError> {
         let word = options
             .as_ref()
@@ -30,15 +31,16 @@ impl Command for Jisho {
 
         let mut jisho_client = JishoClient::new(word);
         jisho_client
-            .api_get_word()
+            .api_get_word(ctx)
             .await
-            .expect("Unable to get word from jisho api");
+            .map_err(|err| InteractionError::UpstreamError(err.to_string()))?;
         let embed = jisho_client.construct_embed().await;
 
         Ok(InteractionApplicationCommandCallbackData {
             content: None,
             choices: None,
             embeds: Some(vec![embed]),
+            flags: None,
         })
     }
 
@@ -64,12 +66,13 @@ impl Command for Jisho {
     async fn autocomplete(
         &self,
         _options: &Option<Vec<ApplicationCommandInteractionDataOption>>,
-        _ctx: &mut worker::RouteContext<()>,
+        _ctx: &mut CommandContext,
     ) -> Result<InteractionApplicationCommandCallbackData, InteractionError> {
         Ok(InteractionApplicationCommandCallbackData {
             content: None,
             embeds: None,
             choices: None,
+            flags: None,
         })
     }
 }
@@ -1,3 +1,7 @@
+pub mod audit;
+pub mod config;
 pub mod hey;
 pub mod jisho;
+pub mod mydata;
+pub mod stats;
 pub mod xe;
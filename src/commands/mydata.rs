@@ -0,0 +1,146 @@
+use crate::command::Command;
+use crate::context::CommandContext;
+use crate::embed::{Embed, EmbedField, DEFAULT_COLOR};
+use crate::error::InteractionError;
+use crate::interaction::{
+    ApplicationCommandInteractionDataOption, ApplicationCommandOption,
+    ApplicationCommandOptionChoice, ApplicationCommandOptionType,
+    InteractionApplicationCommandCallbackData, Member, EPHEMERAL,
+};
+use crate::locale::Text;
+use crate::preferences::UserPreferences;
+use crate::user_data;
+
+use async_trait::async_trait;
+
+/// Embed field values are limited to 1024 characters.
+const MAX_FIELD_LENGTH: usize = 1000;
+
+pub(crate) struct MyData {}
+
+#[async_trait(?Send)]
+impl Command for MyData {
+    async fn respond(
+        &self,
+        _member: &Option<Member>,
+        options: &Option<Vec<ApplicationCommandInteractionDataOption>>,
+        ctx: &mut CommandContext,
+    ) -> Result<InteractionApplicationCommandCallbackData, InteractionError> {
+        let language = ctx.language();
+        let user = ctx
+            .user()
+            .cloned()
+            .ok_or(InteractionError::GenericError())?;
+        let action = options
+            .iter()
+            .flatten()
+            .find(|option| option.name == "action")
+            .and_then(|option| option.value.clone())
+            .unwrap_or_else(|| "show".into());
+
+        // Claims defaults still stored under the username, so they are covered below
+        UserPreferences::load(ctx.store(), &user.id, &user.username).await?;
+
+        let (description, embed_fields) = match action.as_str() {
+            "delete" => {
+                let deleted = user_data::delete_all(ctx.store(), &user.id).await?;
+                log_info!("Deleted user data : {}", deleted.join(", "));
+                (language.text(Text::DataDeleted), vec![])
+            }
+            "export" => {
+                let data = user_data::collect(ctx.store(), &user.id).await?;
+                let export = serde_json::json!({
+                    "user_id": user.id,
+                    "exported_at": chrono::Utc::now().to_rfc3339(),
+                    "data": data,
+                });
+                let content = serde_json::to_vec_pretty(&export)
+                    .map_err(|err| InteractionError::StorageError(err.to_string()))?;
+                ctx.attach(format!("nuko-data-{}.json", user.id), content);
+                (language.text(Text::DataExported), vec![])
+            }
+            _ => {
+                let data = user_data::collect(ctx.store(), &user.id).await?;
+                let fields = data
+                    .iter()
+                    .map(|(kind, value)| {
+                        let mut value = serde_json::to_string_pretty(value).unwrap_or_default();
+                        if value.len() > MAX_FIELD_LENGTH {
+                            let mut end = MAX_FIELD_LENGTH;
+                            while !value.is_char_boundary(end) {
+                                end -= 1;
+                            }
+                            value.truncate(end);
+                            value.push('…');
+                        }
+                        EmbedField {
+                            name: kind.clone(),
+                            value: format!("```json\n{}\n```", value),
+                            inline: Some(false),
+                        }
+                    })
+                    .collect::<Vec<EmbedField>>();
+                if fields.is_empty() {
+                    (language.text(Text::NoData), fields)
+                } else {
+                    (language.text(Text::MyDataHelp), fields)
+                }
+            }
+        };
+
+        Ok(InteractionApplicationCommandCallbackData {
+            content: None,
+            choices: None,
+            embeds: Some(vec![Embed {
+                title: language.text(Text::MyDataTitle).into(),
+                description: description.into(),
+                fields: embed_fields,
+                thumbnail: None,
+                color: Some(DEFAULT_COLOR),
+                url: None,
+                footer: None,
+            }]),
+            flags: Some(EPHEMERAL),
+        })
+    }
+
+    fn name(&self) -> String {
+        "mydata".into()
+    }
+
+    fn description(&self) -> String {
+        "Show, export or delete the data stored about you".into()
+    }
+
+    fn options(&self) -> Option<Vec<ApplicationCommandOption>> {
+        Some(vec![ApplicationCommandOption {
+            name: "action".into(),
+            autocomplete: Some(false),
+            description: "What to do with your data (default: show)".into(),
+            required: Some(false),
+            ty: ApplicationCommandOptionType::String,
+            choices: Some(
+                ["show", "export", "delete"]
+                    .iter()
+                    .map(|action| ApplicationCommandOptionChoice {
+                        name: action.to_string(),
+                        value: action.to_string(),
+                    })
+                    .collect(),
+            ),
+        }])
+    }
+
+    async fn autocomplete(
+        &self,
+        _options: &Option<Vec<ApplicationCommandInteractionDataOption>>,
+        _ctx: &mut CommandContext,
+    ) -> Result<InteractionApplicationCommandCallbackData, InteractionError> {
+        Ok(InteractionApplicationCommandCallbackData {
+            content: None,
+            embeds: None,
+            choices: None,
+            flags: None,
+        })
+    }
+}
@@ -0,0 +1,142 @@
+use std::collections::BTreeMap;
+
+use crate::analytics::{self, DailyStats};
+use crate::command::Command;
+use crate::context::CommandContext;
+use crate::embed::{Embed, EmbedField, DEFAULT_COLOR};
+use crate::error::InteractionError;
+use crate::interaction::{
+    ApplicationCommandInteractionDataOption, ApplicationCommandOption,
+    ApplicationCommandOptionType, InteractionApplicationCommandCallbackData, Member,
+};
+
+use crate::locale::{Language, Text};
+
+use async_trait::async_trait;
+
+const DEFAULT_DAYS: i64 = 7;
+const MAX_DAYS: i64 = 30;
+const TOP: usize = 5;
+
+pub(crate) struct Stats {}
+
+impl Stats {
+    fn top_field(name: &str, counters: &BTreeMap<String, u64>) -> EmbedField {
+        let top = DailyStats::top(counters, TOP)
+            .into_iter()
+            .map(|(key, count)| format!("{} : {}", key, count))
+            .collect::<Vec<String>>();
+
+        EmbedField {
+            name: name.into(),
+            value: if top.is_empty() {
+                "-".into()
+            } else {
+                top.join("\n")
+            },
+            inline: Some(true),
+        }
+    }
+
+    fn cache_field(language: Language, stats: &DailyStats) -> EmbedField {
+        let (hits, lookups) = stats
+            .cache
+            .values()
+            .fold((0, 0), |(hits, lookups), counts| {
+                (
+                    hits + counts.hits + counts.stale,
+                    lookups + counts.hits + counts.stale + counts.misses,
+                )
+            });
+
+        EmbedField {
+            name: language.text(Text::CacheHitRate).into(),
+            value: if lookups == 0 {
+                "-".into()
+            } else {
+                language.hit_rate(hits as f64 / lookups as f64 * 100., lookups)
+            },
+            inline: Some(true),
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl Command for Stats {
+    async fn respond(
+        &self,
+        _member: &Option<Member>,
+        options: &Option<Vec<ApplicationCommandInteractionDataOption>>,
+        ctx: &mut CommandContext,
+    ) -> Result<InteractionApplicationCommandCallbackData, InteractionError> {
+        let days = options
+            .iter()
+            .flatten()
+            .find(|option| option.name == "days")
+            .and_then(|option| option.value.as_ref())
+            .and_then(|days| days.parse::<i64>().ok())
+            .unwrap_or(DEFAULT_DAYS)
+            .clamp(1, MAX_DAYS);
+
+        let language = ctx.language();
+        let mut total = DailyStats::default();
+        for stats in analytics::load_days(ctx.store(), days).await?.values() {
+            total.merge(stats);
+        }
+
+        Ok(InteractionApplicationCommandCallbackData {
+            content: None,
+            choices: None,
+            embeds: Some(vec![Embed {
+                title: language.text(Text::StatsTitle).into(),
+                description: language.stats_summary(
+                    days,
+                    total.commands.values().sum::<u64>(),
+                    total.guilds.len(),
+                ),
+                fields: vec![
+                    Stats::top_field(language.text(Text::TopCommands), &total.commands),
+                    Stats::top_field(language.text(Text::TopPairs), &total.pairs),
+                    Stats::cache_field(language, &total),
+                ],
+                thumbnail: None,
+                color: Some(DEFAULT_COLOR),
+                url: None,
+                footer: None,
+            }]),
+            flags: None,
+        })
+    }
+
+    fn name(&self) -> String {
+        "stats".into()
+    }
+
+    fn description(&self) -> String {
+        "Show which commands and currency pairs are used the most".into()
+    }
+
+    fn options(&self) -> Option<Vec<ApplicationCommandOption>> {
+        Some(vec![ApplicationCommandOption {
+            name: "days".into(),
+            autocomplete: Some(false),
+            description: "How many days to look back (default 7, max 30)".into(),
+            required: Some(false),
+            ty: ApplicationCommandOptionType::String,
+            choices: None,
+        }])
+    }
+
+    async fn autocomplete(
+        &self,
+        _options: &Option<Vec<ApplicationCommandInteractionDataOption>>,
+        _ctx: &mut CommandContext,
+    ) -> Result<InteractionApplicationCommandCallbackData, InteractionError> {
+        Ok(InteractionApplicationCommandCallbackData {
+            content: None,
+            embeds: None,
+            choices: None,
+            flags: None,
+        })
+    }
+}
@@ -1,5 +1,8 @@
+use crate::analytics;
+use crate::audit;
 use crate::command::Command;
-use crate::embed::Embed;
+use crate::context::CommandContext;
+use crate::embed::{Embed, DEFAULT_COLOR};
 use crate::error::InteractionError;
 use crate::helpers::xe_client::XEClient;
 use crate::interaction::{
@@ -7,12 +10,15 @@ use crate::interaction::{
     ApplicationCommandOptionChoice, ApplicationCommandOptionType,
     InteractionApplicationCommandCallbackData, Member,
 };
+use crate::locale::Text;
+use crate::preferences::UserPreferences;
 
 use strum::IntoEnumIterator;
 use strum_macros::{Display, EnumIter};
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Display, EnumIter)]
-enum CurrencyCodes {
+pub(crate) enum CurrencyCodes {
     USD,
     EUR,
     JPY,
@@ -38,6 +44,7 @@ enum CurrencyCodes {
 }
 
 use async_trait::async_trait;
+use serde_json::json;
 
 pub(crate) struct XE {}
 
@@ -45,9 +52,9 @@ pub(crate) struct XE {}
 impl Command for XE {
     async fn respond(
         &self,
-        member: &Option<Member>,
+        _member: &Option<Member>,
         options: &Option<Vec<ApplicationCommandInteractionDataOption>>,
-        ctx: &mut worker::RouteContext<()>,
+        ctx: &mut CommandContext,
     ) -> Result<InteractionApplicationCommandCallbackData, InteractionError> {
         let temp_vec = Vec::new();
         // Create a hash map of the options, so we can easily access them by name
@@ -63,17 +70,19 @@ impl Command for XE {
             })
             .collect::<std::collections::HashMap<&str, String>>();
 
-        let username = member.as_ref().unwrap().user.username.clone();
+        let user = ctx
+            .user()
+            .cloned()
+            .ok_or(InteractionError::GenericError())?;
 
         let is_setting_defaults = match options_hash_map.get("set_defaults") {
             Some(default) => default.contains("True"),
             _ => false,
         };
 
-        let is_getting_timeseries = match options_hash_map.get("timeseries") {
-            Some(x) => true,
-            _ => false,
-        };
+        let is_getting_timeseries = options_hash_map.contains_key("timeseries");
+        let stored = UserPreferences::load(ctx.store(), &user.id, &user.username).await?;
+        let preferences = stored.clone().with_guild_defaults(ctx.guild_config());
 
         let mut xe_client = XEClient::new(
             options_hash_map.get("from"),
@@ -81,56 +90,75 @@ impl Command for XE {
             options_hash_map.get("amount"),
             options_hash_map.get("precision"),
             options_hash_map.get("timeseries"),
-            &ctx.kv("exchange_defaults")?,
-            &username,
+            &preferences,
         )
-        .await;
+        .with_cache_ttls(ctx.config().cache_ttls);
 
         if is_setting_defaults {
-            xe_client
-                .set_default(&ctx.kv("exchange_defaults")?, &username)
-                .await
-                .expect("Unable to set defaults");
+            let updated = xe_client
+                .set_default(ctx.store(), &user.id, &stored)
+                .await?;
+            audit::record(
+                ctx,
+                None,
+                "xe",
+                "set_defaults",
+                json!(stored),
+                json!(updated),
+            )
+            .await?;
 
             return Ok(InteractionApplicationCommandCallbackData {
                 content: None,
                 choices: None,
                 embeds: Some(vec![Embed {
                     title: "Exchange Rate".into(),
-                    description: "Defaults have been updated".into(),
+                    description: ctx.language().text(Text::DefaultsUpdated).into(),
                     fields: vec![],
                     thumbnail: None,
-                    color: Some(0xfdc835),
+                    color: Some(DEFAULT_COLOR),
                     url: None,
                     footer: None,
                 }]),
+                flags: None,
             });
         }
 
+        // Only needed once fixer is called, saving defaults works without it
+        let api_key = ctx
+            .config()
+            .fixer_token
+            .clone()
+            .ok_or_else(|| InteractionError::MissingConfiguration("CURR_CONV_TOKEN".into()))?;
+        let (from, to) = xe_client.pair();
+        analytics::record_pair(ctx, from, to);
+
         if is_getting_timeseries {
             xe_client
-                .get_timeseries(ctx, &ctx.kv("exchange_defaults")?)
+                .get_timeseries(&api_key, ctx)
                 .await
-                .expect("Unable to get timeseries");
+                .map_err(|err| InteractionError::UpstreamError(err.to_string()))?;
             let embed = xe_client.construct_timeseries_embed();
 
             return Ok(InteractionApplicationCommandCallbackData {
                 content: None,
                 choices: None,
                 embeds: Some(vec![embed]),
+                flags: None,
             });
         }
 
         xe_client
-            .get_rate(ctx, &ctx.kv("exchange_defaults")?)
+            .get_rate(&api_key, ctx)
             .await
-            .expect("Unable to get exchange rate from api");
+            .map_err(|err| InteractionError::UpstreamError(err.to_string()))?;
         let embed = xe_client.construct_rate_embed();
 
         Ok(InteractionApplicationCommandCallbackData {
             content: None,
             choices: None,
             embeds: Some(vec![embed]),
+            flags: None,
         })
     }
 
@@ -216,12 +244,13 @@ impl Command for XE {
     async fn autocomplete(
         &self,
         _options: &Option<Vec<ApplicationCommandInteractionDataOption>>,
-        _ctx: &mut worker::RouteContext<()>,
+        _ctx: &mut CommandContext,
     ) -> Result<InteractionApplicationCommandCallbackData, InteractionError> {
         Ok(InteractionApplicationCommandCallbackData {
             content: None,
             embeds: None,
             choices: None,
+            flags: None,
         })
     }
 }
@@ -0,0 +1,411 @@
+//! Typed configuration, read once from Workers vars and secrets, or from the
+//! environment when running natively.
+//!
+//! Every key is listed in [`KEYS`]. Required keys must be set, everything else
+//! falls back to a default, and values that do not parse are rejected rather
+//! than ignored. Everything wrong is reported at once in a [`ConfigError`].
+
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::discord::webhook_parts;
+use crate::guard::DEFAULT_MAX_BODY_BYTES;
+use crate::helpers::xe_client::CacheTtls;
+use crate::job::find_job;
+use crate::logger::{Format, Level};
+use crate::store::MIN_TTL;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::upstream::recording;
+use crate::upstream::Upstream;
+use crate::verification::cached_public_keys;
+
+/// Every var and secret the bot reads.
+pub(crate) const KEYS: [&str; 24] = [
+    "DISCORD_PUBLIC_KEY",
+    "DISCORD_APPLICATION_ID",
+    "DISCORD_TOKEN",
+    "CURR_CONV_TOKEN",
+    "ADMIN_TOKEN",
+    "AUDIT_WEBHOOK_URL",
+    "ERROR_WEBHOOK_URL",
+    "ERROR_REPORT_INTERVAL",
+    "LOG_LEVEL",
+    "LOG_FORMAT",
+    "RATE_CACHE_TTL",
+    "RATE_STALE_TTL",
+    "TIMESERIES_CACHE_TTL",
+    "TIMESERIES_PAST_CACHE_TTL",
+    "SIGNATURE_MAX_AGE",
+    "DEDUPLICATE_INTERACTIONS",
+    "GUILD_COMMANDS",
+    "JOB_SCHEDULE",
+    "MAX_BODY_BYTES",
+    "JISHO_API_BASE",
+    "FIXER_API_BASE",
+    "DISCORD_API_BASE",
+    "UPSTREAM_MODE",
+    "UPSTREAM_FIXTURES",
+];
+
+/// Keys read as secrets, set with `wrangler secret put` rather than in wrangler.toml.
+const SECRETS: [&str; 5] = [
+    "DISCORD_TOKEN",
+    "CURR_CONV_TOKEN",
+    "ADMIN_TOKEN",
+    "AUDIT_WEBHOOK_URL",
+    "ERROR_WEBHOOK_URL",
+];
+
+const DEFAULT_SIGNATURE_MAX_AGE: i64 = 300;
+const DEFAULT_ERROR_REPORT_INTERVAL: i64 = 10 * 60;
+
+/// Every required key that is missing and every value that is invalid.
+#[derive(Debug, Default)]
+pub(crate) struct ConfigError {
+    pub(crate) missing: Vec<&'static str>,
+    pub(crate) invalid: Vec<(&'static str, String)>,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut problems = Vec::new();
+        if !self.missing.is_empty() {
+            problems.push(format!("missing {}", self.missing.join(", ")));
+        }
+        for (key, reason) in &self.invalid {
+            problems.push(format!("{} is invalid ({})", key, reason));
+        }
+        write!(f, "Configuration error: {}", problems.join("; "))
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Where upstream responses come from, see [`crate::upstream::client_for`].
+/// Workers have no file system, so they always go live.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum UpstreamMode {
+    Live,
+    Record,
+    Replay,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl FromStr for UpstreamMode {
+    type Err = String;
+
+    fn from_str(mode: &str) -> Result<Self, Self::Err> {
+        match mode.trim().to_ascii_lowercase().as_str() {
+            "live" => Ok(UpstreamMode::Live),
+            "record" => Ok(UpstreamMode::Record),
+            "replay" => Ok(UpstreamMode::Replay),
+            other => Err(format!("unknown upstream mode '{}'", other)),
+        }
+    }
+}
+
+/// Which jobs run on which cron trigger, written as `name=cron` pairs
+/// separated by `;`, such as `register_commands=0 */6 * * *`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct JobSchedule(Vec<(String, String)>);
+
+impl JobSchedule {
+    /// The jobs scheduled with exactly `cron`. Cloudflare hands back the
+    /// expression as written in wrangler.toml, so it is not normalized.
+    pub(crate) fn jobs_for(&self, cron: &str) -> Vec<&str> {
+        self.0
+            .iter()
+            .filter(|(_, scheduled)| scheduled == cron.trim())
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+}
+
+impl FromStr for JobSchedule {
+    type Err = String;
+
+    fn from_str(schedule: &str) -> Result<Self, Self::Err> {
+        let mut jobs = Vec::new();
+        for entry in schedule.split(';').filter(|entry| !entry.trim().is_empty()) {
+            let (name, cron) = entry
+                .split_once('=')
+                .map(|(name, cron)| (name.trim(), cron.trim()))
+                .ok_or_else(|| format!("expected name=cron, got '{}'", entry.trim()))?;
+            if find_job(name).is_none() {
+                return Err(format!("unknown job '{}'", name));
+            }
+            if cron.split_whitespace().count() != 5 {
+                return Err(format!("'{}' is not a cron expression", cron));
+            }
+            jobs.push((name.to_string(), cron.to_string()));
+        }
+        Ok(JobSchedule(jobs))
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct Config {
+    /// Comma separated, so the key can be rotated.
+    pub(crate) discord_public_key: String,
+    pub(crate) discord_application_id: String,
+    pub(crate) discord_token: String,
+    /// The fixer API key, `/xe` is unavailable without one.
+    pub(crate) fixer_token: Option<String>,
+    /// Enables the admin endpoints and `/diagnostics`.
+    pub(crate) admin_token: Option<String>,
+    pub(crate) audit_webhook_url: Option<String>,
+    pub(crate) error_webhook_url: Option<String>,
+    /// Seconds between reports of the same kind of failure.
+    pub(crate) error_report_interval: i64,
+    pub(crate) log_level: Level,
+    pub(crate) log_format: Format,
+    pub(crate) cache_ttls: CacheTtls,
+    /// How far `x-signature-timestamp` may drift from the current time, in seconds.
+    pub(crate) signature_max_age: i64,
+    pub(crate) max_body_bytes: usize,
+    /// Also reject interaction IDs that were already handled.
+    pub(crate) deduplicate_interactions: bool,
+    /// Register commands per guild, leaving out the ones a guild has disabled.
+    pub(crate) guild_commands: bool,
+    pub(crate) job_schedule: JobSchedule,
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) upstream_mode: UpstreamMode,
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) upstream_fixtures: String,
+    base_urls: HashMap<Upstream, String>,
+    set: Vec<&'static str>,
+}
+
+/// Reads keys, collecting whatever is wrong with them along the way.
+struct Reader<'a> {
+    var: &'a dyn Fn(&str) -> Option<String>,
+    secret: &'a dyn Fn(&str) -> Option<String>,
+    error: ConfigError,
+    set: Vec<&'static str>,
+}
+
+impl Reader<'_> {
+    /// A key's value, `None` when unset or empty.
+    fn optional(&mut self, key: &'static str) -> Option<String> {
+        let value = if SECRETS.contains(&key) {
+            (self.secret)(key)
+        } else {
+            (self.var)(key)
+        }
+        .filter(|value| !value.trim().is_empty());
+        if value.is_some() {
+            self.set.push(key);
+        }
+        value
+    }
+
+    fn required(&mut self, key: &'static str) -> String {
+        self.optional(key).unwrap_or_else(|| {
+            self.error.missing.push(key);
+            String::new()
+        })
+    }
+
+    fn parse<T>(&mut self, key: &'static str, default: T) -> T
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        match self.optional(key).map(|value| value.trim().parse::<T>()) {
+            Some(Ok(value)) => value,
+            Some(Err(err)) => {
+                self.error.invalid.push((key, err.to_string()));
+                default
+            }
+            None => default,
+        }
+    }
+
+    fn check<T, F>(&mut self, key: &'static str, value: &Option<T>, valid: F, reason: &str)
+    where
+        F: Fn(&T) -> bool,
+    {
+        if value.as_ref().is_some_and(|value| !valid(value)) {
+            self.error.invalid.push((key, reason.into()));
+        }
+    }
+}
+
+impl Config {
+    /// Reads every key, failing when a required one is missing or any is invalid.
+    pub(crate) fn load<V, S>(var: V, secret: S) -> Result<Self, ConfigError>
+    where
+        V: Fn(&str) -> Option<String>,
+        S: Fn(&str) -> Option<String>,
+    {
+        Self::read(&var, &secret, true)
+    }
+
+    /// Like [`Config::load`] for running commands without Discord, where the
+    /// Discord keys may be left unset.
+    #[cfg(any(feature = "native", test))]
+    pub(crate) fn without_discord<V>(var: V) -> Result<Self, ConfigError>
+    where
+        V: Fn(&str) -> Option<String>,
+    {
+        Self::read(&var, &var, false)
+    }
+
+    pub(crate) fn from_env(env: &worker::Env) -> Result<Self, ConfigError> {
+        Self::load(
+            |key| env.var(key).ok().map(|var| var.to_string()),
+            |key| env.secret(key).ok().map(|secret| secret.to_string()),
+        )
+    }
+
+    fn read(
+        var: &dyn Fn(&str) -> Option<String>,
+        secret: &dyn Fn(&str) -> Option<String>,
+        require_discord: bool,
+    ) -> Result<Self, ConfigError> {
+        let mut reader = Reader {
+            var,
+            secret,
+            error: ConfigError::default(),
+            set: Vec::new(),
+        };
+
+        let (discord_public_key, discord_application_id, discord_token) = if require_discord {
+            (
+                reader.required("DISCORD_PUBLIC_KEY"),
+                reader.required("DISCORD_APPLICATION_ID"),
+                reader.required("DISCORD_TOKEN"),
+            )
+        } else {
+            (
+                reader.optional("DISCORD_PUBLIC_KEY").unwrap_or_default(),
+                reader
+                    .optional("DISCORD_APPLICATION_ID")
+                    .unwrap_or_default(),
+                reader.optional("DISCORD_TOKEN").unwrap_or_default(),
+            )
+        };
+        let public_key = Some(discord_public_key.clone()).filter(|key| !key.is_empty());
+        reader.check(
+            "DISCORD_PUBLIC_KEY",
+            &public_key,
+            // Cached, as the configuration is read again on every request
+            |keys| cached_public_keys(keys).is_ok(),
+            "expected hex encoded ed25519 keys",
+        );
+        let application_id = Some(discord_application_id.clone()).filter(|id| !id.is_empty());
+        reader.check(
+            "DISCORD_APPLICATION_ID",
+            &application_id,
+            |id| id.chars().all(|c| c.is_ascii_digit()),
+            "expected a numeric ID",
+        );
+
+        let fixer_token = reader.optional("CURR_CONV_TOKEN");
+        let admin_token = reader.optional("ADMIN_TOKEN");
+        let audit_webhook_url = reader.optional("AUDIT_WEBHOOK_URL");
+        reader.check(
+            "AUDIT_WEBHOOK_URL",
+            &audit_webhook_url,
+            |url| webhook_parts(url).is_some(),
+            "expected a webhook URL",
+        );
+        let error_webhook_url = reader.optional("ERROR_WEBHOOK_URL");
+        reader.check(
+            "ERROR_WEBHOOK_URL",
+            &error_webhook_url,
+            |url| webhook_parts(url).is_some(),
+            "expected a webhook URL",
+        );
+
+        let defaults = CacheTtls::default();
+        let cache_ttls = CacheTtls {
+            rate: reader.parse("RATE_CACHE_TTL", defaults.rate),
+            rate_stale: reader.parse("RATE_STALE_TTL", defaults.rate_stale),
+            timeseries_current: reader.parse("TIMESERIES_CACHE_TTL", defaults.timeseries_current),
+            timeseries_past: reader.parse("TIMESERIES_PAST_CACHE_TTL", defaults.timeseries_past),
+        };
+        // KV refuses shorter TTLs
+        let cache_ttls = CacheTtls {
+            rate: cache_ttls.rate.max(MIN_TTL),
+            rate_stale: cache_ttls.rate_stale.max(MIN_TTL),
+            timeseries_current: cache_ttls.timeseries_current.max(MIN_TTL),
+            timeseries_past: cache_ttls.timeseries_past.max(MIN_TTL),
+        };
+
+        let mut base_urls = HashMap::new();
+        for upstream in Upstream::ALL.iter() {
+            let base_url = reader.optional(upstream.base_url_var());
+            reader.check(
+                upstream.base_url_var(),
+                &base_url,
+                |url| url.starts_with("http://") || url.starts_with("https://"),
+                "expected an http or https URL",
+            );
+            let base_url = base_url.unwrap_or_else(|| upstream.default_base_url().into());
+            base_urls.insert(*upstream, base_url.trim_end_matches('/').to_string());
+        }
+
+        let config = Config {
+            discord_public_key,
+            discord_application_id,
+            discord_token,
+            fixer_token,
+            admin_token,
+            audit_webhook_url,
+            error_webhook_url,
+            error_report_interval: reader
+                .parse("ERROR_REPORT_INTERVAL", DEFAULT_ERROR_REPORT_INTERVAL),
+            log_level: reader.parse("LOG_LEVEL", Level::Info),
+            log_format: reader.parse("LOG_FORMAT", Format::Json),
+            cache_ttls,
+            signature_max_age: reader.parse("SIGNATURE_MAX_AGE", DEFAULT_SIGNATURE_MAX_AGE),
+            max_body_bytes: reader.parse("MAX_BODY_BYTES", DEFAULT_MAX_BODY_BYTES),
+            deduplicate_interactions: reader.parse("DEDUPLICATE_INTERACTIONS", false),
+            guild_commands: reader.parse("GUILD_COMMANDS", false),
+            job_schedule: reader.parse("JOB_SCHEDULE", JobSchedule::default()),
+            #[cfg(not(target_arch = "wasm32"))]
+            upstream_mode: reader.parse("UPSTREAM_MODE", UpstreamMode::Live),
+            #[cfg(not(target_arch = "wasm32"))]
+            upstream_fixtures: reader
+                .optional("UPSTREAM_FIXTURES")
+                .unwrap_or_else(|| recording::DEFAULT_DIR.into()),
+            base_urls,
+            set: reader.set,
+        };
+
+        let error = reader.error;
+        if error.missing.is_empty() && error.invalid.is_empty() {
+            Ok(config)
+        } else {
+            Err(error)
+        }
+    }
+
+    /// The API root requests to `upstream` are sent to, without a trailing slash.
+    pub(crate) fn base_url(&self, upstream: Upstream) -> &str {
+        &self.base_urls[&upstream]
+    }
+
+    /// Whether `key` was set, without giving its value away.
+    pub(crate) fn is_set(&self, key: &'static str) -> bool {
+        self.set.contains(&key)
+    }
+
+    /// Values to scrub from logs.
+    pub(crate) fn secrets(&self) -> Vec<String> {
+        vec![
+            Some(self.discord_token.clone()),
+            self.fixer_token.clone(),
+            self.admin_token.clone(),
+            self.audit_webhook_url.clone(),
+            self.error_webhook_url.clone(),
+        ]
+        .into_iter()
+        .flatten()
+        .filter(|secret| !secret.is_empty())
+        .collect()
+    }
+}
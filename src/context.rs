@@ -0,0 +1,198 @@
+//! The runtime services handed to commands, kept free of Workers types so
+//! commands can run anywhere a [`Store`] and a [`Config`] are available.
+
+use std::cell::RefCell;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+
+use worker::RouteContext;
+
+use crate::analytics::DailyStats;
+use crate::cache::CacheMetrics;
+use crate::config::Config;
+use crate::guild_config::GuildConfig;
+use crate::interaction::{Outcome, ResponseFile, User};
+use crate::locale::Language;
+use crate::store::{Store, WorkersKvStore, NAMESPACE};
+use crate::upstream::{self, HttpClient, Upstream, UpstreamCall, UpstreamClient};
+
+type Task = Pin<Box<dyn Future<Output = ()>>>;
+
+/// Work that should finish after the response has been sent, such as cache
+/// refreshes. On Workers it is handed to `wait_until`.
+#[derive(Clone, Default)]
+pub(crate) struct Background {
+    tasks: Rc<RefCell<Vec<Task>>>,
+}
+
+impl Background {
+    pub(crate) fn spawn<F>(&self, task: F)
+    where
+        F: Future<Output = ()> + 'static,
+    {
+        self.tasks.borrow_mut().push(Box::pin(task));
+    }
+
+    /// Runs every queued task, including any queued while running.
+    pub(crate) async fn run(self) {
+        loop {
+            let tasks = std::mem::take(&mut *self.tasks.borrow_mut());
+            if tasks.is_empty() {
+                return;
+            }
+            for task in tasks {
+                task.await;
+            }
+        }
+    }
+}
+
+/// What the Workers router hands to every route.
+#[derive(Clone)]
+pub(crate) struct RouteData {
+    pub(crate) background: Background,
+    pub(crate) config: Rc<Config>,
+}
+
+pub(crate) struct CommandContext {
+    store: Rc<dyn Store>,
+    config: Rc<Config>,
+    http: Rc<dyn HttpClient>,
+    background: Background,
+    cache_metrics: Rc<RefCell<CacheMetrics>>,
+    upstream_calls: Rc<RefCell<Vec<UpstreamCall>>>,
+    outcome: Option<Outcome>,
+    user: Option<User>,
+    guild_id: Option<String>,
+    guild_config: GuildConfig,
+    files: Vec<ResponseFile>,
+    usage: DailyStats,
+}
+
+impl CommandContext {
+    pub(crate) fn new(store: Rc<dyn Store>, config: Rc<Config>, background: Background) -> Self {
+        let http = upstream::client_for(&config);
+        Self {
+            store,
+            config,
+            http,
+            background,
+            cache_metrics: Rc::new(RefCell::new(CacheMetrics::default())),
+            upstream_calls: Rc::default(),
+            outcome: None,
+            user: None,
+            guild_id: None,
+            guild_config: GuildConfig::default(),
+            files: Vec::new(),
+            usage: DailyStats::default(),
+        }
+    }
+
+    pub(crate) fn from_route(ctx: &RouteContext<RouteData>) -> worker::Result<Self> {
+        Ok(CommandContext::new(
+            Rc::new(WorkersKvStore::new(ctx.kv(NAMESPACE)?)),
+            ctx.data.config.clone(),
+            ctx.data.background.clone(),
+        ))
+    }
+
+    pub(crate) fn store(&self) -> &dyn Store {
+        self.store.as_ref()
+    }
+
+    /// A handle on the store that can outlive the current command, for background work.
+    pub(crate) fn shared_store(&self) -> Rc<dyn Store> {
+        self.store.clone()
+    }
+
+    pub(crate) fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Sends upstream requests through `http` instead of the one picked by
+    /// `UPSTREAM_MODE`.
+    #[allow(dead_code)]
+    pub(crate) fn with_http(mut self, http: Rc<dyn HttpClient>) -> Self {
+        self.http = http;
+        self
+    }
+
+    pub(crate) fn upstream(&self, upstream: Upstream) -> UpstreamClient {
+        UpstreamClient::new(
+            self.http.clone(),
+            self.shared_store(),
+            upstream,
+            self.config.base_url(upstream).to_string(),
+        )
+        .with_calls(self.upstream_calls.clone())
+    }
+
+    pub(crate) fn background(&self) -> &Background {
+        &self.background
+    }
+
+    pub(crate) fn cache_metrics(&self) -> &RefCell<CacheMetrics> {
+        &self.cache_metrics
+    }
+
+    /// Calls made to upstreams so far, in the order they finished.
+    pub(crate) fn upstream_calls(&self) -> &RefCell<Vec<UpstreamCall>> {
+        &self.upstream_calls
+    }
+
+    /// Notes how the interaction was answered when it was not simply answered.
+    pub(crate) fn set_outcome(&mut self, outcome: Outcome) {
+        self.outcome = Some(outcome);
+    }
+
+    pub(crate) fn outcome(&self) -> Option<Outcome> {
+        self.outcome
+    }
+
+    pub(crate) fn set_user(&mut self, user: User) {
+        self.user = Some(user);
+    }
+
+    /// The user who invoked the current interaction.
+    pub(crate) fn user(&self) -> Option<&User> {
+        self.user.as_ref()
+    }
+
+    /// Sets the guild the current interaction came from, along with its configuration.
+    pub(crate) fn set_guild(&mut self, guild_id: String, config: GuildConfig) {
+        self.guild_id = Some(guild_id);
+        self.guild_config = config;
+    }
+
+    pub(crate) fn guild_id(&self) -> Option<&str> {
+        self.guild_id.as_deref()
+    }
+
+    /// The configuration of the current guild, empty outside of guilds.
+    pub(crate) fn guild_config(&self) -> &GuildConfig {
+        &self.guild_config
+    }
+
+    pub(crate) fn language(&self) -> Language {
+        self.guild_config.language.unwrap_or_default()
+    }
+
+    /// Sends a file along with the response to the current command.
+    pub(crate) fn attach(&mut self, filename: String, content: Vec<u8>) {
+        self.files.push(ResponseFile { filename, content });
+    }
+
+    pub(crate) fn take_files(&mut self) -> Vec<ResponseFile> {
+        std::mem::take(&mut self.files)
+    }
+
+    /// Usage noted by the current command, recorded once it has finished.
+    pub(crate) fn usage_mut(&mut self) -> &mut DailyStats {
+        &mut self.usage
+    }
+
+    pub(crate) fn take_usage(&mut self) -> DailyStats {
+        std::mem::take(&mut self.usage)
+    }
+}
@@ -0,0 +1,141 @@
+//! What `GET /health`, `GET /version` and `GET /diagnostics` report about a
+//! deployment.
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::analytics;
+use crate::cache::CacheCounts;
+use crate::circuit_breaker::CircuitState;
+use crate::command::registered_commands;
+use crate::config::KEYS;
+use crate::context::CommandContext;
+use crate::job::{init_jobs, JobRun};
+use crate::store::StoreError;
+use crate::upstream::Upstream;
+
+/// Read to check that the store answers, never written.
+const PROBE_KEY: &str = "health:probe";
+
+#[derive(Serialize)]
+pub(crate) struct Health {
+    pub(crate) status: &'static str,
+    pub(crate) store: &'static str,
+}
+
+impl Health {
+    /// Reads from the store, the only dependency every interaction needs.
+    pub(crate) async fn check(ctx: &CommandContext) -> Self {
+        match ctx.store().get(PROBE_KEY).await {
+            Ok(_) => Health {
+                status: "ok",
+                store: "ok",
+            },
+            Err(err) => {
+                log_error!("Health check could not read the store : {}", err);
+                Health {
+                    status: "unavailable",
+                    store: "unreachable",
+                }
+            }
+        }
+    }
+
+    pub(crate) fn is_ok(&self) -> bool {
+        self.status == "ok"
+    }
+}
+
+#[derive(Serialize)]
+pub(crate) struct CommandVersion {
+    pub(crate) name: String,
+    pub(crate) hash: String,
+}
+
+#[derive(Serialize)]
+pub(crate) struct Version {
+    pub(crate) version: &'static str,
+    pub(crate) commit: &'static str,
+    pub(crate) commands: Vec<CommandVersion>,
+}
+
+impl Version {
+    pub(crate) fn current() -> Self {
+        Version {
+            version: env!("CARGO_PKG_VERSION"),
+            commit: env!("NUKO_GIT_COMMIT"),
+            commands: registered_commands()
+                .iter()
+                .map(|command| CommandVersion {
+                    name: command.name.clone(),
+                    hash: command.hash(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Which keys are set, by name only.
+#[derive(Serialize)]
+pub(crate) struct Vars {
+    pub(crate) set: Vec<&'static str>,
+    pub(crate) unset: Vec<&'static str>,
+}
+
+#[derive(Serialize)]
+pub(crate) struct Circuit {
+    pub(crate) open: bool,
+    pub(crate) failures: u32,
+    pub(crate) open_until: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub(crate) struct Diagnostics {
+    pub(crate) vars: Vars,
+    pub(crate) circuits: BTreeMap<String, Circuit>,
+    /// Today's cache lookups by namespace.
+    pub(crate) cache: BTreeMap<String, CacheCounts>,
+    /// The last run of each job, `null` for jobs that never ran.
+    pub(crate) jobs: BTreeMap<String, Option<JobRun>>,
+}
+
+impl Diagnostics {
+    pub(crate) async fn collect(ctx: &CommandContext) -> Result<Self, StoreError> {
+        let (set, unset) = KEYS
+            .iter()
+            .partition::<Vec<&str>, _>(|key| ctx.config().is_set(key));
+
+        let now = chrono::Utc::now().timestamp();
+        let mut circuits = BTreeMap::new();
+        for upstream in Upstream::ALL.iter() {
+            let state = CircuitState::load(ctx.store(), *upstream).await?;
+            circuits.insert(
+                upstream.to_string(),
+                Circuit {
+                    open: state.is_open(now),
+                    failures: state.failures,
+                    open_until: state.open_until,
+                },
+            );
+        }
+
+        let mut jobs = BTreeMap::new();
+        for job in init_jobs().iter() {
+            jobs.insert(
+                job.name().to_string(),
+                JobRun::load(ctx.store(), job.name()).await?,
+            );
+        }
+
+        let today = analytics::recent_days(1).remove(0);
+        let cache = analytics::load_day(ctx.store(), &today).await?.cache;
+
+        Ok(Diagnostics {
+            vars: Vars { set, unset },
+            circuits,
+            cache,
+            jobs,
+        })
+    }
+}
@@ -0,0 +1,218 @@
+use std::cell::RefCell;
+use std::time::Duration;
+
+use reqwest::{Method, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use super::models::{ApplicationCommand, Message, MessagePayload, PartialGuild};
+use super::rate_limit::{seconds_to_millis, RateLimitHeaders, RateLimiter};
+use crate::command::RegisteredCommand;
+use crate::utils::sleep;
+
+/// Requests that are rate limited more often than this are given up on.
+const MAX_ATTEMPTS: usize = 3;
+
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum DiscordError {
+    #[error("Request to Discord failed: {0}")]
+    RequestFailed(#[from] reqwest::Error),
+
+    #[error("Failed to deserialize from or serialize to JSON.")]
+    JsonFailed(#[from] serde_json::Error),
+
+    #[error("Discord responded with {status}: {message}")]
+    Api { status: u16, message: String },
+
+    #[error("Rate limited by Discord, retry after {0:?}")]
+    RateLimited(Duration),
+
+    #[error("A bot token is required for this request.")]
+    MissingToken,
+}
+
+#[derive(Deserialize)]
+struct ApiError {
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct RateLimitedBody {
+    retry_after: f64,
+}
+
+pub(crate) struct DiscordClient {
+    client: reqwest::Client,
+    base_url: String,
+    token: Option<String>,
+    max_wait: Duration,
+    limiter: RefCell<RateLimiter>,
+}
+
+impl DiscordClient {
+    pub(crate) const DEFAULT_BASE_URL: &'static str = "https://discord.com/api/v10";
+
+    pub(crate) fn new(token: Option<String>) -> Self {
+        Self {
+            client: crate::upstream::shared_client(),
+            base_url: DiscordClient::DEFAULT_BASE_URL.into(),
+            token,
+            max_wait: Duration::from_secs(10),
+            limiter: RefCell::new(RateLimiter::default()),
+        }
+    }
+
+    /// Points the client at another API root, such as a local stand-in server.
+    pub(crate) fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    // Application commands
+
+    pub(crate) async fn bulk_overwrite_global_commands(
+        &self,
+        application_id: &str,
+        commands: &[RegisteredCommand],
+    ) -> Result<Vec<ApplicationCommand>, DiscordError> {
+        let path = format!("/applications/{}/commands", application_id);
+        self.send(Method::PUT, &path, Some(&commands), true).await
+    }
+
+    pub(crate) async fn bulk_overwrite_guild_commands(
+        &self,
+        application_id: &str,
+        guild_id: &str,
+        commands: &[RegisteredCommand],
+    ) -> Result<Vec<ApplicationCommand>, DiscordError> {
+        let path = format!(
+            "/applications/{}/guilds/{}/commands",
+            application_id, guild_id
+        );
+        self.send(Method::PUT, &path, Some(&commands), true).await
+    }
+
+    // Guilds
+
+    /// A page of the guilds the bot is in, ordered by ID and starting after `after`.
+    pub(crate) async fn get_current_user_guilds(
+        &self,
+        after: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<PartialGuild>, DiscordError> {
+        let mut path = format!("/users/@me/guilds?limit={}", limit);
+        if let Some(after) = after {
+            path.push_str(&format!("&after={}", after));
+        }
+        self.send(Method::GET, &path, None::<&()>, true).await
+    }
+
+    // Webhooks
+
+    /// Executes a webhook and waits for the created message.
+    pub(crate) async fn execute_webhook(
+        &self,
+        webhook_id: &str,
+        webhook_token: &str,
+        message: &MessagePayload,
+    ) -> Result<Message, DiscordError> {
+        let path = format!("/webhooks/{}/{}?wait=true", webhook_id, webhook_token);
+        self.send(Method::POST, &path, Some(message), false).await
+    }
+
+    async fn send<B: Serialize + ?Sized, T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&B>,
+        authorized: bool,
+    ) -> Result<T, DiscordError> {
+        let text = self.execute(method, path, body, authorized).await?;
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    async fn execute<B: Serialize + ?Sized>(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&B>,
+        authorized: bool,
+    ) -> Result<String, DiscordError> {
+        let route = format!("{} {}", method, path);
+        let body = body.map(serde_json::to_string).transpose()?;
+
+        let mut retry_after = Duration::from_secs(0);
+        for attempt in 1..=MAX_ATTEMPTS {
+            let delay = self.limiter.borrow().delay_for(&route, now());
+            if let Some(delay) = delay {
+                self.wait(delay).await?;
+            }
+
+            let mut request = self
+                .client
+                .request(method.clone(), format!("{}{}", self.base_url, path));
+            if authorized {
+                let token = self.token.as_ref().ok_or(DiscordError::MissingToken)?;
+                request = request.header("Authorization", format!("Bot {}", token));
+            }
+            if let Some(body) = &body {
+                request = request
+                    .header("Content-Type", "application/json")
+                    .body(body.clone());
+            }
+
+            let response = request.send().await?;
+            let status = response.status();
+            let headers = RateLimitHeaders::parse(response.headers());
+            self.limiter.borrow_mut().update(&route, &headers, now());
+            let text = response.text().await?;
+
+            if status == StatusCode::TOO_MANY_REQUESTS {
+                // The body is more precise than the header, which is rounded to whole seconds
+                let seconds = serde_json::from_str::<RateLimitedBody>(&text)
+                    .map(|body| body.retry_after)
+                    .ok()
+                    .or(headers.retry_after)
+                    .unwrap_or(1.);
+                retry_after = Duration::from_millis(seconds_to_millis(seconds) as u64);
+                // No point waiting when there is no attempt left to make
+                if attempt < MAX_ATTEMPTS {
+                    self.wait(retry_after).await?;
+                }
+                continue;
+            }
+
+            if !status.is_success() {
+                return Err(DiscordError::Api {
+                    status: status.as_u16(),
+                    message: serde_json::from_str::<ApiError>(&text)
+                        .map(|error| error.message)
+                        .unwrap_or(text),
+                });
+            }
+
+            return Ok(text);
+        }
+
+        Err(DiscordError::RateLimited(retry_after))
+    }
+
+    async fn wait(&self, delay: Duration) -> Result<(), DiscordError> {
+        if delay > self.max_wait {
+            return Err(DiscordError::RateLimited(delay));
+        }
+        sleep(delay).await;
+        Ok(())
+    }
+}
+
+fn now() -> i64 {
+    chrono::Utc::now().timestamp_millis()
+}
+
+/// The ID and token of a `.../webhooks/{id}/{token}` URL.
+pub(crate) fn webhook_parts(url: &str) -> Option<(&str, &str)> {
+    let mut segments = url.trim_end_matches('/').rsplit('/');
+    let (token, id) = (segments.next()?, segments.next()?);
+    (segments.next() == Some("webhooks")).then_some((id, token))
+}
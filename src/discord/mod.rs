@@ -0,0 +1,12 @@
+//! A small typed client for the parts of the Discord REST API the bot uses.
+//!
+//! Everything goes through [`DiscordClient`], which takes care of the bot
+//! authorization header and of Discord's rate limits.
+
+mod client;
+mod models;
+mod rate_limit;
+
+pub(crate) use client::DiscordError;
+pub(crate) use client::{webhook_parts, DiscordClient};
+pub(crate) use models::{ApplicationCommand, MessagePayload};
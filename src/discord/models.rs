@@ -0,0 +1,45 @@
+use serde::{Deserialize, Serialize};
+
+use crate::embed::Embed;
+use crate::interaction::ApplicationCommandOption;
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct ApplicationCommand {
+    // https://discord.com/developers/docs/interactions/application-commands#application-command-object-application-command-structure
+    pub(crate) id: String,
+    pub(crate) application_id: String,
+    pub(crate) guild_id: Option<String>,
+    pub(crate) name: String,
+    pub(crate) description: String,
+    pub(crate) options: Option<Vec<ApplicationCommandOption>>,
+    pub(crate) version: String,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct PartialGuild {
+    // https://discord.com/developers/docs/resources/user#get-current-user-guilds
+    pub(crate) id: String,
+    pub(crate) name: String,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct Message {
+    // https://discord.com/developers/docs/resources/channel#message-object-message-structure
+    pub(crate) id: String,
+    pub(crate) channel_id: String,
+    pub(crate) content: String,
+    pub(crate) timestamp: String,
+    pub(crate) webhook_id: Option<String>,
+}
+
+#[derive(Serialize, Default)]
+pub(crate) struct MessagePayload {
+    // Shared by channel messages, interaction follow-ups and webhooks, which all accept
+    // the same subset of fields
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) content: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) embeds: Option<Vec<Embed>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) flags: Option<u64>,
+}
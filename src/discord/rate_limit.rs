@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use reqwest::header::HeaderMap;
+
+// https://discord.com/developers/docs/topics/rate-limits#header-format
+const REMAINING: &str = "x-ratelimit-remaining";
+const RESET_AFTER: &str = "x-ratelimit-reset-after";
+const BUCKET: &str = "x-ratelimit-bucket";
+const GLOBAL: &str = "x-ratelimit-global";
+const RETRY_AFTER: &str = "retry-after";
+
+#[derive(Debug, Default)]
+pub(crate) struct RateLimitHeaders {
+    pub(crate) remaining: Option<u32>,
+    pub(crate) reset_after: Option<f64>,
+    pub(crate) bucket: Option<String>,
+    pub(crate) global: bool,
+    pub(crate) retry_after: Option<f64>,
+}
+
+impl RateLimitHeaders {
+    pub(crate) fn parse(headers: &HeaderMap) -> Self {
+        let get = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.trim().to_string())
+        };
+
+        RateLimitHeaders {
+            remaining: get(REMAINING).and_then(|v| v.parse().ok()),
+            reset_after: get(RESET_AFTER).and_then(|v| v.parse().ok()),
+            bucket: get(BUCKET),
+            global: get(GLOBAL).map(|v| v == "true").unwrap_or(false),
+            retry_after: get(RETRY_AFTER).and_then(|v| v.parse().ok()),
+        }
+    }
+}
+
+struct BucketState {
+    remaining: u32,
+    reset_at: i64,
+}
+
+/// Tracks the buckets Discord reports so requests can wait out an exhausted
+/// bucket instead of being rejected with a 429.
+///
+/// Times are unix milliseconds rather than `Instant`s, which are not available
+/// on the Workers runtime.
+#[derive(Default)]
+pub(crate) struct RateLimiter {
+    routes: HashMap<String, String>,
+    buckets: HashMap<String, BucketState>,
+    global_reset_at: Option<i64>,
+}
+
+impl RateLimiter {
+    /// How long to wait before `route` may be requested again, if at all.
+    pub(crate) fn delay_for(&self, route: &str, now: i64) -> Option<Duration> {
+        let bucket_reset_at = self
+            .routes
+            .get(route)
+            .and_then(|bucket| self.buckets.get(bucket))
+            .filter(|state| state.remaining == 0)
+            .map(|state| state.reset_at);
+
+        let reset_at = match (bucket_reset_at, self.global_reset_at) {
+            (Some(bucket), Some(global)) => bucket.max(global),
+            (bucket, global) => bucket.or(global)?,
+        };
+
+        if reset_at > now {
+            Some(Duration::from_millis((reset_at - now) as u64))
+        } else {
+            None
+        }
+    }
+
+    /// Records the rate-limit headers of a response to `route`.
+    pub(crate) fn update(&mut self, route: &str, headers: &RateLimitHeaders, now: i64) {
+        if headers.global {
+            if let Some(retry_after) = headers.retry_after {
+                self.global_reset_at = Some(now + seconds_to_millis(retry_after));
+            }
+            return;
+        }
+
+        let bucket = match &headers.bucket {
+            Some(bucket) => bucket.clone(),
+            None => return,
+        };
+        self.routes.insert(route.to_string(), bucket.clone());
+
+        if let (Some(remaining), Some(reset_after)) = (headers.remaining, headers.reset_after) {
+            self.buckets.insert(
+                bucket,
+                BucketState {
+                    remaining,
+                    reset_at: now + seconds_to_millis(reset_after),
+                },
+            );
+        }
+    }
+}
+
+pub(crate) fn seconds_to_millis(seconds: f64) -> i64 {
+    (seconds * 1000.).ceil() as i64
+}
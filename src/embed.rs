@@ -1,5 +1,12 @@
 use serde::Serialize;
 
+/// The bot's own color, replaced by a guild's `embed_color` when one is set.
+/// Embeds colored on purpose, such as errors, keep their color.
+pub(crate) const DEFAULT_COLOR: u32 = 0xfdc835;
+
+/// Error reports posted to the error webhook.
+pub(crate) const ERROR_COLOR: u32 = 0xe74c3c;
+
 #[derive(Serialize)]
 pub(crate) struct Thumbnail {
     pub(crate) url: String,
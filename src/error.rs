@@ -1,30 +1,46 @@
+use crate::store::StoreError;
 use crate::verification::VerificationError;
 
-#[derive(Debug, thiserror::Error)]
+#[derive(Debug, thiserror::Error, strum_macros::IntoStaticStr)]
+#[strum(serialize_all = "snake_case")]
 pub(crate) enum Error {
-    #[error("Environment variable '{0}' not found.")]
-    EnvironmentVariableNotFound(String),
+    #[error("Invalid configuration: {0}")]
+    InvalidConfiguration(String),
 
     #[error("Header '{0}' not found.")]
     HeaderNotFound(String),
 
+    #[error("Header '{0}' is invalid: {1}.")]
+    InvalidHeader(String, String),
+
+    #[error("Method '{0}' is not allowed.")]
+    MethodNotAllowed(String),
+
+    #[error("Content type '{0}' is not supported, expected application/json.")]
+    UnsupportedContentType(String),
+
+    #[error("Payload of {0} bytes exceeds the limit of {1} bytes.")]
+    PayloadTooLarge(usize, usize),
+
     #[error("Failed to deserialize from or serialize to JSON.")]
     JsonFailed(#[from] serde_json::Error),
 
     #[error("Invalid payload provided: {0}.")]
     InvalidPayload(String),
 
+    #[error("Storage failed: {0}")]
+    StorageFailed(String),
+
     #[error("Verification failed.")]
     VerificationFailed(VerificationError),
 
     #[error("Interaction failed.")]
-    InteractionFailed(InteractionError)
+    InteractionFailed(InteractionError),
 }
 
-#[derive(Debug, thiserror::Error)]
+#[derive(Debug, thiserror::Error, strum_macros::IntoStaticStr)]
+#[strum(serialize_all = "snake_case")]
 pub(crate) enum InteractionError {
-
-    #[allow(dead_code)]
     #[error("Error communicating with {0}")]
     UpstreamError(String),
 
@@ -35,11 +51,39 @@ pub(crate) enum InteractionError {
     GenericError(),
 
     #[error("Cloudflare worker error: {0}")]
-    WorkerError(String)
+    WorkerError(String),
+
+    #[error("Storage error: {0}")]
+    StorageError(String),
+
+    #[error("Configuration '{0}' is missing")]
+    MissingConfiguration(String),
+}
+
+impl Error {
+    /// A short name for the kind of failure, such as `storage_error`.
+    pub(crate) fn kind(&self) -> &'static str {
+        match self {
+            Error::InteractionFailed(err) => err.into(),
+            err => err.into(),
+        }
+    }
+}
+
+impl From<StoreError> for Error {
+    fn from(error: StoreError) -> Error {
+        Error::StorageFailed(error.to_string())
+    }
+}
+
+impl From<StoreError> for InteractionError {
+    fn from(error: StoreError) -> InteractionError {
+        InteractionError::StorageError(error.to_string())
+    }
 }
 
 impl From<worker::Error> for InteractionError {
     fn from(error: worker::Error) -> InteractionError {
         InteractionError::WorkerError(format!("{}", error))
     }
-}
\ No newline at end of file
+}
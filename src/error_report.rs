@@ -0,0 +1,187 @@
+//! Posts failed interactions to an operators' channel through the webhook at
+//! `ERROR_WEBHOOK_URL`.
+//!
+//! Failures are grouped by kind, and each kind is reported at most once per
+//! `ERROR_REPORT_INTERVAL` seconds. Later reports say how many were held back
+//! in between. Like the circuit breaker, the throttle lives in the store, so
+//! isolates racing each other may occasionally both report.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::context::CommandContext;
+use crate::discord::{webhook_parts, DiscordClient, MessagePayload};
+use crate::embed::{Embed, EmbedField, ERROR_COLOR};
+use crate::logger;
+use crate::store::migration::{read_record, write_record, Record};
+use crate::store::{Store, StoreError};
+use crate::upstream::Upstream;
+
+/// Seconds a throttle is kept, long enough to still count held back failures
+/// when the next report goes out.
+const THROTTLE_TTL: u64 = 24 * 60 * 60;
+/// Discord rejects embed fields longer than 1024 characters.
+const MAX_FIELD_CHARS: usize = 1000;
+
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+pub(crate) struct ReportThrottle {
+    /// Unix seconds of the last report sent for this kind.
+    pub(crate) reported_at: i64,
+    /// Failures of this kind since then that were not reported.
+    pub(crate) held_back: u32,
+}
+
+impl Record for ReportThrottle {
+    const KIND: &'static str = "error report throttle";
+    const PREFIX: &'static str = "error_report:";
+    const VERSION: u32 = 1;
+    const TTL: Option<u64> = Some(THROTTLE_TTL);
+
+    fn upgrade(version: u32, _value: Value) -> Result<Value, StoreError> {
+        Err(StoreError::UnsupportedVersion(Self::KIND.into(), version))
+    }
+}
+
+/// An interaction that failed, or that could only be answered with a notice.
+#[derive(Default)]
+pub(crate) struct Failure {
+    /// Groups failures that should be reported together, such as `fixer_429`.
+    pub(crate) kind: String,
+    pub(crate) interaction_id: Option<String>,
+    pub(crate) command: Option<String>,
+    /// The options the command was given, as `name=value` pairs.
+    pub(crate) options: Option<String>,
+    pub(crate) guild_id: Option<String>,
+    /// The error along with everything it was wrapped in.
+    pub(crate) context: String,
+}
+
+/// Reports `failure` in the background, unless no webhook is configured or
+/// the same kind was reported recently.
+pub(crate) fn report(ctx: &CommandContext, failure: Failure) {
+    let config = ctx.config();
+    let url = match config.error_webhook_url.clone() {
+        Some(url) => url,
+        None => return,
+    };
+    let interval = config.error_report_interval;
+    let client = DiscordClient::new(None).with_base_url(config.base_url(Upstream::Discord));
+    // Scrub now, the interaction token is only known to the logger until the
+    // next request starts
+    let failure = Failure {
+        options: failure.options.map(|options| logger::redact(&options)),
+        context: logger::redact(&failure.context),
+        ..failure
+    };
+
+    let store = ctx.shared_store();
+    ctx.background().spawn(async move {
+        let now = chrono::Utc::now().timestamp();
+        let held_back = match throttle(store.as_ref(), &failure.kind, now, interval).await {
+            Ok(Some(held_back)) => held_back,
+            Ok(None) => return,
+            Err(err) => {
+                log_warn!("Unable to throttle error reports : {}", err);
+                0
+            }
+        };
+        send(&client, &url, &failure, held_back).await;
+    });
+}
+
+/// Notes a failure of `kind`, returning how many were held back since the
+/// last report when this one should be reported, `None` otherwise.
+async fn throttle(
+    store: &dyn Store,
+    kind: &str,
+    now: i64,
+    interval: i64,
+) -> Result<Option<u32>, StoreError> {
+    let key = format!("{}{}", ReportThrottle::PREFIX, kind);
+    let throttle = read_record::<ReportThrottle>(store, &key).await?;
+    let (state, due) = match throttle {
+        Some(throttle) if now - throttle.reported_at < interval => (
+            ReportThrottle {
+                held_back: throttle.held_back + 1,
+                ..throttle
+            },
+            None,
+        ),
+        throttle => (
+            ReportThrottle {
+                reported_at: now,
+                held_back: 0,
+            },
+            Some(throttle.map(|throttle| throttle.held_back).unwrap_or(0)),
+        ),
+    };
+    write_record(store, &key, &state, ReportThrottle::TTL).await?;
+    Ok(due)
+}
+
+fn field(name: &str, value: &str) -> EmbedField {
+    let mut value = value.chars().take(MAX_FIELD_CHARS).collect::<String>();
+    if value.is_empty() {
+        value = "-".into();
+    }
+    EmbedField {
+        name: name.into(),
+        value,
+        inline: Some(true),
+    }
+}
+
+async fn send(client: &DiscordClient, url: &str, failure: &Failure, held_back: u32) {
+    let (id, token) = match webhook_parts(url) {
+        Some(parts) => parts,
+        None => {
+            log_warn!("ERROR_WEBHOOK_URL is not a webhook URL");
+            return;
+        }
+    };
+
+    // Cut before wrapping, so the code block is always closed
+    let context = failure
+        .context
+        .chars()
+        .take(MAX_FIELD_CHARS - 6)
+        .collect::<String>();
+    let mut fields = vec![
+        field("Command", failure.command.as_deref().unwrap_or_default()),
+        field("Options", failure.options.as_deref().unwrap_or_default()),
+        field(
+            "Guild",
+            failure.guild_id.as_deref().unwrap_or("Direct message"),
+        ),
+        field(
+            "Interaction",
+            failure.interaction_id.as_deref().unwrap_or_default(),
+        ),
+        EmbedField {
+            inline: Some(false),
+            ..field("Context", &format!("```{}```", context))
+        },
+    ];
+    if held_back > 0 {
+        fields.push(field(
+            "Held back",
+            &format!("{} more since the last report", held_back),
+        ));
+    }
+
+    let payload = MessagePayload {
+        embeds: Some(vec![Embed {
+            title: format!("Interaction failed: {}", failure.kind),
+            description: String::new(),
+            fields,
+            thumbnail: None,
+            color: Some(ERROR_COLOR),
+            url: None,
+            footer: None,
+        }]),
+        ..MessagePayload::default()
+    };
+    if let Err(err) = client.execute_webhook(id, token, &payload).await {
+        log_warn!("Unable to report a failed interaction : {}", err);
+    }
+}
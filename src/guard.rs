@@ -0,0 +1,99 @@
+use crate::error::Error;
+
+/// Interaction payloads are a few kilobytes at most, anything far beyond that
+/// is not coming from Discord.
+pub(crate) const DEFAULT_MAX_BODY_BYTES: usize = 64 * 1024;
+
+const SIGNATURE_HEADER: &str = "x-signature-ed25519";
+const TIMESTAMP_HEADER: &str = "x-signature-timestamp";
+/// An ed25519 signature is 64 bytes, hex encoded.
+const SIGNATURE_HEX_LENGTH: usize = 128;
+/// Enough digits for any unix timestamp in seconds.
+const MAX_TIMESTAMP_LENGTH: usize = 12;
+
+/// The signature headers of a request that passed [`RequestGuard::check_head`].
+pub(crate) struct SignedHeaders {
+    pub(crate) signature: String,
+    pub(crate) timestamp: String,
+}
+
+/// Cheap checks run on an incoming interaction request before its body is
+/// read or its signature verified.
+pub(crate) struct RequestGuard {
+    max_body_bytes: usize,
+}
+
+impl RequestGuard {
+    pub(crate) fn new(max_body_bytes: usize) -> Self {
+        Self { max_body_bytes }
+    }
+
+    /// Checks the method and headers, returning the signature headers.
+    pub(crate) fn check_head<F>(&self, method: &str, header: F) -> Result<SignedHeaders, Error>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        if !method.eq_ignore_ascii_case("POST") {
+            return Err(Error::MethodNotAllowed(method.to_string()));
+        }
+
+        let content_type = header("content-type").unwrap_or_default();
+        let mime = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        if mime != "application/json" {
+            return Err(Error::UnsupportedContentType(content_type));
+        }
+
+        if let Some(content_length) = header("content-length") {
+            let content_length = content_length.trim().parse::<usize>().map_err(|_| {
+                Error::InvalidHeader("content-length".into(), "not a number".into())
+            })?;
+            self.check_length(content_length)?;
+        }
+
+        let signature = header(SIGNATURE_HEADER)
+            .ok_or_else(|| Error::HeaderNotFound(SIGNATURE_HEADER.into()))?;
+        if signature.len() != SIGNATURE_HEX_LENGTH
+            || !signature.chars().all(|c| c.is_ascii_hexdigit())
+        {
+            return Err(Error::InvalidHeader(
+                SIGNATURE_HEADER.into(),
+                format!("expected {} hex characters", SIGNATURE_HEX_LENGTH),
+            ));
+        }
+
+        let timestamp = header(TIMESTAMP_HEADER)
+            .ok_or_else(|| Error::HeaderNotFound(TIMESTAMP_HEADER.into()))?;
+        if timestamp.is_empty()
+            || timestamp.len() > MAX_TIMESTAMP_LENGTH
+            || !timestamp.chars().all(|c| c.is_ascii_digit())
+        {
+            return Err(Error::InvalidHeader(
+                TIMESTAMP_HEADER.into(),
+                "expected a unix timestamp".into(),
+            ));
+        }
+
+        Ok(SignedHeaders {
+            signature,
+            timestamp,
+        })
+    }
+
+    /// Checks the body once read, for requests that did not send `content-length`.
+    pub(crate) fn check_body(&self, body: Vec<u8>) -> Result<String, Error> {
+        self.check_length(body.len())?;
+        String::from_utf8(body).map_err(|_| Error::InvalidPayload("body is not valid UTF-8".into()))
+    }
+
+    fn check_length(&self, length: usize) -> Result<(), Error> {
+        if length > self.max_body_bytes {
+            return Err(Error::PayloadTooLarge(length, self.max_body_bytes));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,70 @@
+//! Per-guild settings, managed with `/config` and used wherever a user has not
+//! set a default of their own.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::locale::Language;
+use crate::store::migration::{read_record, write_record, Record};
+use crate::store::{Store, StoreError};
+
+pub(crate) const ADMINISTRATOR: u64 = 1 << 3;
+pub(crate) const MANAGE_GUILD: u64 = 1 << 5;
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Default)]
+pub(crate) struct GuildConfig {
+    pub(crate) currency_from: Option<String>,
+    pub(crate) currency_to: Option<String>,
+    pub(crate) precision: Option<usize>,
+    pub(crate) embed_color: Option<u32>,
+    pub(crate) language: Option<Language>,
+    /// Commands that answer with a notice instead of running in this guild.
+    #[serde(default)]
+    pub(crate) disabled_commands: Vec<String>,
+}
+
+impl Record for GuildConfig {
+    const KIND: &'static str = "guild config";
+    const PREFIX: &'static str = "guild_config:";
+    const VERSION: u32 = 1;
+
+    fn upgrade(version: u32, _value: Value) -> Result<Value, StoreError> {
+        Err(StoreError::UnsupportedVersion(Self::KIND.into(), version))
+    }
+}
+
+pub(crate) fn guild_config_key(guild_id: &str) -> String {
+    format!("{}{}", GuildConfig::PREFIX, guild_id)
+}
+
+impl GuildConfig {
+    pub(crate) async fn load(store: &dyn Store, guild_id: &str) -> Result<Self, StoreError> {
+        Ok(read_record(store, &guild_config_key(guild_id))
+            .await?
+            .unwrap_or_default())
+    }
+
+    pub(crate) async fn save(&self, store: &dyn Store, guild_id: &str) -> Result<(), StoreError> {
+        write_record(store, &guild_config_key(guild_id), self, None).await
+    }
+
+    pub(crate) fn is_enabled(&self, command: &str) -> bool {
+        !self.disabled_commands.iter().any(|name| name == command)
+    }
+}
+
+/// Commands a guild can never disable: `/config` itself, or nobody could turn
+/// it back on, and `/mydata`, a user's only way to export or delete their data.
+const ALWAYS_ENABLED: [&str; 2] = ["config", "mydata"];
+
+pub(crate) fn can_disable(command: &str) -> bool {
+    !ALWAYS_ENABLED.contains(&command)
+}
+
+/// Whether a member's permission bitfield, as sent with the interaction,
+/// includes any of `required`. Administrators have every permission.
+pub(crate) fn has_permission(permissions: &str, required: u64) -> bool {
+    permissions
+        .parse::<u64>()
+        .is_ok_and(|permissions| permissions & (required | ADMINISTRATOR) != 0)
+}
@@ -1,16 +1,25 @@
-use reqwest;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+use crate::cache::{cached, CachePolicy};
+use crate::context::CommandContext;
 use crate::embed::{Embed, EmbedField};
+use crate::upstream::{query, Upstream, UpstreamClient};
 
-#[derive(Deserialize)]
+/// Dictionary entries rarely change, so they are kept for a day and served
+/// while being refreshed for a week after that.
+const CACHE_POLICY: CachePolicy = CachePolicy {
+    fresh_for: 24 * 60 * 60,
+    stale_for: 7 * 24 * 60 * 60,
+};
+
+#[derive(Deserialize, Serialize)]
 pub struct Response {
     meta: Meta,
     data: Vec<Data>,
 }
 
-#[derive(Deserialize, Clone)]
+#[derive(Deserialize, Serialize, Clone)]
 pub struct Data {
     slug: String,
     is_common: bool,
@@ -21,20 +30,20 @@ pub struct Data {
     attribution: Attribution,
 }
 
-#[derive(Deserialize, Clone)]
+#[derive(Deserialize, Serialize, Clone)]
 pub struct Attribution {
     jmdict: bool,
     jmnedict: bool,
     dbpedia: Value,
 }
 
-#[derive(Deserialize, Clone)]
+#[derive(Deserialize, Serialize, Clone)]
 pub struct Japanese {
     word: Option<String>,
     reading: Option<String>,
 }
 
-#[derive(Deserialize, Clone)]
+#[derive(Deserialize, Serialize, Clone)]
 pub struct Sense {
     english_definitions: Vec<String>,
     parts_of_speech: Vec<String>,
@@ -48,19 +57,19 @@ pub struct Sense {
     sentences: Option<Vec<Value>>,
 }
 
-#[derive(Deserialize, Clone)]
+#[derive(Deserialize, Serialize, Clone)]
 pub struct Link {
     text: String,
     url: String,
 }
 
-#[derive(Deserialize, Clone)]
+#[derive(Deserialize, Serialize, Clone)]
 pub struct Source {
     language: String,
     word: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 pub struct Meta {
     status: usize,
 }
@@ -71,7 +80,6 @@ pub struct RequestOptions {
 }
 
 pub struct JishoClient {
-    client: reqwest::Client,
     options: RequestOptions,
     response: Option<Response>,
 }
@@ -79,29 +87,39 @@ pub struct JishoClient {
 impl JishoClient {
     pub fn new(word: String) -> Self {
         Self {
-            client: reqwest::Client::new(),
             options: RequestOptions { word },
             response: None,
         }
     }
 
-    pub async fn api_get_word(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        let res = self
-            .client
-            .get(format!(
-                "https://jisho.org/api/v1/search/words?keyword={}",
-                self.options.word
-            ))
-            .send()
-            .await?
-            .json::<Response>()
-            .await?;
+    pub async fn api_get_word(
+        &mut self,
+        ctx: &CommandContext,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let upstream = ctx.upstream(Upstream::Jisho);
+        let word = self.options.word.clone();
+        let res = cached(
+            ctx,
+            "jisho",
+            &self.options.word.to_lowercase(),
+            CACHE_POLICY,
+            move || JishoClient::fetch_word(upstream, word),
+        )
+        .await?;
 
         self.response = Some(res);
 
         Ok(())
     }
 
+    async fn fetch_word(
+        upstream: UpstreamClient,
+        word: String,
+    ) -> Result<Response, Box<dyn std::error::Error>> {
+        let path = format!("/search/words{}", query(&[("keyword", &word)]));
+        Ok(upstream.get_json::<Response>(&path, &[]).await?)
+    }
+
     pub(crate) async fn construct_embed(&self) -> Embed {
         match &self.response {
             Some(res) => match res.data.clone().into_iter().next() {
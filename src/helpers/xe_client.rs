@@ -2,13 +2,21 @@ use std::collections::HashMap;
 
 use chrono;
 use rasciigraph::{plot, Config};
-use reqwest;
 use serde::Deserialize;
 use serde_json::Value;
-use worker::kv::KvStore;
-use worker::RouteContext;
 
-use crate::embed::{Embed, EmbedField};
+use crate::cache::{cached, CachePolicy};
+use crate::context::CommandContext;
+use crate::embed::{Embed, EmbedField, DEFAULT_COLOR};
+use crate::preferences::{TimeseriesOffset, UserPreferences};
+use crate::store::{Store, StoreError};
+use crate::upstream::{query, Upstream, UpstreamClient};
+
+/// Used when neither the user nor their guild has set a default.
+const DEFAULT_FROM: &str = "USD";
+const DEFAULT_TO: &str = "JPY";
+const DEFAULT_PRECISION: usize = 4;
+const DEFAULT_TIMESERIES_OFFSET: TimeseriesOffset = TimeseriesOffset { start: 0, end: -21 };
 
 pub struct XEOptions {
     set_default: Option<bool>,
@@ -53,132 +61,116 @@ pub struct TimeseriesRequest {
     end_date: String,
 }
 
+/// How long fetched rates are kept, in seconds.
+#[derive(Clone, Copy, Debug)]
+pub struct CacheTtls {
+    /// Latest rates.
+    pub rate: u64,
+    /// How long after that a latest rate is still shown while it is refreshed.
+    pub rate_stale: u64,
+    /// Timeseries whose range includes today, which can still change.
+    pub timeseries_current: u64,
+    /// Timeseries whose range ended before today, which will never change.
+    pub timeseries_past: u64,
+}
+
+impl Default for CacheTtls {
+    fn default() -> Self {
+        Self {
+            rate: 4 * 60 * 60,
+            rate_stale: 24 * 60 * 60,
+            timeseries_current: 60 * 60,
+            timeseries_past: 30 * 24 * 60 * 60,
+        }
+    }
+}
+
+/// Which options were passed to the command, rather than filled in from
+/// defaults.
+#[derive(Default)]
+struct Given {
+    from: bool,
+    to: bool,
+    precision: bool,
+    dates: bool,
+}
+
 pub struct XEClient {
-    client: reqwest::Client,
     request: Request,
+    given: Given,
+    cache_ttls: CacheTtls,
     rate: Option<f64>,
     timeseries: Option<TimeseriesResponse>,
 }
 
 impl XEClient {
-    pub async fn new(
+    pub fn new(
         from: Option<&String>,
         to: Option<&String>,
         amount: Option<&String>,
         precision: Option<&String>,
         dates: Option<&String>,
-        kv: &KvStore,
-        username: &String,
+        preferences: &UserPreferences,
     ) -> Self {
         Self {
-            client: reqwest::Client::new(),
             request: Request {
-                from: XEClient::resolve_from(from, kv, username)
-                    .await
-                    .unwrap_or("USD".into()),
-                to: XEClient::resolve_to(to, kv, username)
-                    .await
-                    .unwrap_or("JPY".into()),
-                amount: XEClient::resolve_amount(amount).await,
-                precision: XEClient::resolve_precision(precision, kv, username)
-                    .await
-                    .unwrap_or(4),
-                dates: XEClient::resolve_dates(dates, kv, username)
-                    .await
-                    .unwrap_or(TimeseriesRequest {
-                        start_date: "2022-01-01".into(),
-                        end_date: "2022-02-01".into(),
-                    }),
+                from: XEClient::resolve_from(from, preferences),
+                to: XEClient::resolve_to(to, preferences),
+                amount: XEClient::resolve_amount(amount),
+                precision: XEClient::resolve_precision(precision, preferences),
+                dates: XEClient::resolve_dates(dates, preferences),
+            },
+            given: Given {
+                from: from.is_some(),
+                to: to.is_some(),
+                precision: precision.is_some(),
+                dates: dates.is_some(),
             },
+            cache_ttls: CacheTtls::default(),
             rate: None,
             timeseries: None,
         }
     }
 
-    async fn resolve_from(
-        from: Option<&String>,
-        kv: &KvStore,
-        username: &String,
-    ) -> Result<String, Box<dyn std::error::Error>> {
-        if from.is_some() {
-            return Ok(from.unwrap_or(&"USD".into()).to_owned());
-        }
+    pub fn with_cache_ttls(mut self, cache_ttls: CacheTtls) -> Self {
+        self.cache_ttls = cache_ttls;
+        self
+    }
 
-        let key = format!("{}:currency_from", username);
-        Ok(kv
-            .get(key.as_str())
-            .text()
-            .await?
-            .unwrap_or("USD".into())
-            .into())
+    /// The currencies being converted, from and to.
+    pub(crate) fn pair(&self) -> (&str, &str) {
+        (&self.request.from, &self.request.to)
     }
 
-    async fn resolve_to(
-        to: Option<&String>,
-        kv: &KvStore,
-        username: &String,
-    ) -> Result<String, Box<dyn std::error::Error>> {
-        if to.is_some() {
-            return Ok(to.unwrap_or(&"JPY".into()).to_owned());
-        }
+    fn resolve_from(from: Option<&String>, preferences: &UserPreferences) -> String {
+        from.or(preferences.currency_from.as_ref())
+            .cloned()
+            .unwrap_or_else(|| DEFAULT_FROM.into())
+    }
 
-        let key = format!("{}:currency_to", username);
-        Ok(kv
-            .get(key.as_str())
-            .text()
-            .await?
-            .unwrap_or("JPY".into())
-            .into())
+    fn resolve_to(to: Option<&String>, preferences: &UserPreferences) -> String {
+        to.or(preferences.currency_to.as_ref())
+            .cloned()
+            .unwrap_or_else(|| DEFAULT_TO.into())
     }
 
-    async fn resolve_precision(
-        precision: Option<&String>,
-        kv: &KvStore,
-        username: &String,
-    ) -> Result<usize, Box<dyn std::error::Error>> {
-        if precision.is_some() {
-            return Ok(precision
-                .unwrap_or(&"4".into())
-                .parse::<usize>()
-                .unwrap_or(4));
+    fn resolve_precision(precision: Option<&String>, preferences: &UserPreferences) -> usize {
+        match precision {
+            Some(precision) => precision.parse::<usize>().unwrap_or(DEFAULT_PRECISION),
+            None => preferences.precision.unwrap_or(DEFAULT_PRECISION),
         }
-
-        let key = format!("{}:currency_precision", username);
-        Ok(kv
-            .get(key.as_str())
-            .text()
-            .await?
-            .unwrap_or("4".into())
-            .parse::<usize>()
-            .unwrap_or(4))
     }
 
-    async fn resolve_amount(amount: Option<&String>) -> f64 {
+    fn resolve_amount(amount: Option<&String>) -> f64 {
         amount.unwrap_or(&"1".into()).parse::<f64>().unwrap_or(1.)
     }
 
-    async fn resolve_dates(
-        dates: Option<&String>,
-        kv: &KvStore,
-        username: &String,
-    ) -> Result<TimeseriesRequest, Box<dyn std::error::Error>> {
-        let timeseries_default_key = format!("{}:timeseries_offset", username);
-
-        let timeseries_cache = kv.get(timeseries_default_key.as_str()).text().await?;
-        let timeseries_cache_split = match &timeseries_cache {
-            Some(cache) => cache.split('_').collect::<Vec<&str>>(),
-            None => vec!["0"],
-        };
-
-        let cache_default_start_offset = match timeseries_cache_split.get(0) {
-            Some(offset) => offset.parse::<i64>().unwrap_or(0),
-            None => 0,
-        };
-
-        let cache_default_end_offset = match timeseries_cache_split.get(1) {
-            Some(offset) => offset.parse::<i64>().unwrap_or(-21),
-            None => -21,
-        };
+    fn resolve_dates(dates: Option<&String>, preferences: &UserPreferences) -> TimeseriesRequest {
+        let offset = preferences
+            .timeseries_offset
+            .unwrap_or(DEFAULT_TIMESERIES_OFFSET);
+        let cache_default_start_offset = offset.start;
+        let cache_default_end_offset = offset.end;
 
         let default_end_date = (chrono::Utc::today()
             + chrono::Duration::days(cache_default_end_offset))
@@ -197,7 +189,7 @@ impl XEClient {
                 let split_dates = dates.split('_').collect::<Vec<&str>>();
 
                 TimeseriesRequest {
-                    start_date: match split_dates.get(0) {
+                    start_date: match split_dates.first() {
                         Some(start_date) => {
                             if start_date == &"default" {
                                 default_start_date
@@ -219,15 +211,15 @@ impl XEClient {
             },
         };
 
-        worker::console_log!("Decided that timesries should be: {:?}", &timeseries);
+        log_debug!("Decided that timeseries should be: {:?}", &timeseries);
 
-        Ok(timeseries)
+        timeseries
     }
 
     pub async fn get_timeseries(
         &mut self,
-        ctx: &mut RouteContext<()>,
-        kv: &KvStore,
+        api_key: &str,
+        ctx: &CommandContext,
     ) -> Result<(), Box<dyn std::error::Error>> {
         let timeseries_cache_key = format!(
             "{}_{}_{}_{}",
@@ -237,148 +229,164 @@ impl XEClient {
             self.request.to
         );
 
-        // Get the cache
-        let timeseries_cache = kv
-            .get(format!("timeseries_cache:{}", timeseries_cache_key).as_str())
-            .text()
-            .await?;
+        let upstream = ctx.upstream(Upstream::Fixer);
+        let request = self.request.clone();
+        let api_key = api_key.to_string();
+        let rates = cached(
+            ctx,
+            "xe_timeseries",
+            &timeseries_cache_key,
+            self.timeseries_policy(),
+            move || XEClient::fetch_timeseries(upstream, request, api_key),
+        )
+        .await?;
 
-        // Get cache if exists
-        if timeseries_cache.is_some() {
-            let timeseries_cache = timeseries_cache.unwrap();
-            let timeseries_cache: TimeseriesResponse =
-                serde_json::from_str(timeseries_cache.as_str())?;
+        self.timeseries = Some(rates);
 
-            self.timeseries = Some(timeseries_cache);
-            return Ok(());
-        };
+        Ok(())
+    }
 
-        let api_key = ctx.var("CURR_CONV_TOKEN")?.to_string();
-        let res = self
-            .client
-            .get(format!(
-                "https://api.apilayer.com/fixer/timeseries?symbols={}&base={}&start_date={}&end_date={}",
-                self.request.to, self.request.from, self.request.dates.start_date, self.request.dates.end_date
-            ))
-            .header("apiKey", api_key)
-            .send()
-            .await?
-            .json::<FixerTimeseriesResponse>()
+    async fn fetch_timeseries(
+        upstream: UpstreamClient,
+        request: Request,
+        api_key: String,
+    ) -> Result<TimeseriesResponse, Box<dyn std::error::Error>> {
+        let path = format!(
+            "/timeseries{}",
+            query(&[
+                ("symbols", &request.to),
+                ("base", &request.from),
+                ("start_date", &request.dates.start_date),
+                ("end_date", &request.dates.end_date),
+            ])
+        );
+        let res = upstream
+            .get_json::<FixerTimeseriesResponse>(&path, &[("apiKey", &api_key)])
             .await?;
 
-        worker::console_log!("Currency converter timeseries body : {:?}", res);
-
-        let rates = res.rates;
+        log_debug!("Currency converter timeseries body : {:?}", res);
 
-        // Set the cache
-        if !rates.is_empty() {
-            kv.put(
-                format!("timeseries_cache:{}", timeseries_cache_key.clone()).as_str(),
-                serde_json::to_string(&rates)
-                    .expect("Could not convert timeseries rates to json string")
-                    .as_str(), // Serialise this
-            )?
-            .execute()
-            .await?;
-            self.timeseries = Some(rates);
+        // Empty results are not worth caching
+        if res.rates.is_empty() {
+            return Err("No rates in timeseries response".into());
         }
 
-        Ok(())
+        Ok(res.rates)
     }
 
     pub async fn get_rate(
         &mut self,
-        ctx: &mut RouteContext<()>,
-        kv: &KvStore,
+        api_key: &str,
+        ctx: &CommandContext,
     ) -> Result<(), Box<dyn std::error::Error>> {
         let conversion_key = format!("{}_{}", self.request.from, self.request.to);
 
-        // Get rate cache and compare its timestamp
-        // If over 4 hours ago fetch agains
-        let rate_cache = kv
-            .get(format!("cache:{}", conversion_key).as_str())
-            .text()
-            .await?;
-        if rate_cache.is_some() {
-            let rate_cache = rate_cache.unwrap();
-            let rate_cache: Value = serde_json::from_str(rate_cache.as_str())?;
-            let timestamp = rate_cache["timestamp"].as_u64().unwrap();
-            let now = chrono::Utc::now().timestamp() as u64;
-            if now - timestamp < 14400 {
-                self.rate = Some(rate_cache["rate"].as_f64().unwrap());
-                return Ok(());
-            }
-        }
+        let upstream = ctx.upstream(Upstream::Fixer);
+        let request = self.request.clone();
+        let api_key = api_key.to_string();
+        let rate = cached(
+            ctx,
+            "xe_rate",
+            &conversion_key,
+            CachePolicy::new(self.cache_ttls.rate, self.cache_ttls.rate_stale),
+            move || XEClient::fetch_rate(upstream, request, api_key),
+        )
+        .await?;
 
-        let api_key = ctx.var("CURR_CONV_TOKEN")?.to_string();
-        let res = self
-            .client
-            .get(format!(
-                "https://api.apilayer.com/fixer/latest?symbols={}&base={}",
-                self.request.to, self.request.from
-            ))
-            .header("apiKey", api_key)
-            .send()
-            .await?
-            .json::<FixerResponse>()
-            .await?;
+        self.rate = Some(rate);
+
+        Ok(())
+    }
 
-        worker::console_log!("Currency converter body : {:?}", res);
-
-        let rate: Option<f64> = res.rates[self.request.to.clone().as_str()].as_f64();
-
-        // Cache rate
-        if rate.is_some() {
-            let rate = rate.unwrap();
-            let rate_cache = serde_json::json!({
-                "rate": rate,
-                "timestamp": chrono::Utc::now().timestamp()
-            });
-            kv.put(
-                format!("cache:{}", conversion_key.clone()).as_str(),
-                rate_cache.to_string().as_str(),
-            )?
-            .execute()
+    async fn fetch_rate(
+        upstream: UpstreamClient,
+        request: Request,
+        api_key: String,
+    ) -> Result<f64, Box<dyn std::error::Error>> {
+        let path = format!(
+            "/latest{}",
+            query(&[("symbols", &request.to), ("base", &request.from)])
+        );
+        let res = upstream
+            .get_json::<FixerResponse>(&path, &[("apiKey", &api_key)])
             .await?;
-            self.rate = Some(rate);
-        }
 
-        self.rate = rate;
+        log_debug!("Currency converter body : {:?}", res);
 
-        Ok(())
+        res.rates[request.to.as_str()]
+            .as_f64()
+            .ok_or_else(|| format!("No rate for {} in response", request.to).into())
     }
 
+    /// Saves the options that were passed as the user's defaults, keeping their
+    /// earlier defaults for the rest. Guild defaults are never copied, so later
+    /// changes to them still apply.
     pub(crate) async fn set_default(
         &self,
-        kv: &KvStore,
-        username: &String,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        let to_key = format!("{}:currency_to", username);
-        let from_key = format!("{}:currency_from", username);
-        let precision_key = format!("{}:currency_precision", username);
-        let timeseries_key = format!("{}:timeseries_offset", username);
+        store: &dyn Store,
+        user_id: &str,
+        stored: &UserPreferences,
+    ) -> Result<UserPreferences, StoreError> {
+        let preferences = UserPreferences {
+            currency_from: if self.given.from {
+                Some(self.request.from.clone())
+            } else {
+                stored.currency_from.clone()
+            },
+            currency_to: if self.given.to {
+                Some(self.request.to.clone())
+            } else {
+                stored.currency_to.clone()
+            },
+            precision: if self.given.precision {
+                Some(self.request.precision)
+            } else {
+                stored.precision
+            },
+            timeseries_offset: if self.given.dates {
+                self.timeseries_offset()
+            } else {
+                stored.timeseries_offset
+            },
+        };
+        preferences.save(store, user_id).await?;
 
-        kv.put(to_key.as_str(), self.request.to.clone())?
-            .execute()
-            .await?;
-        kv.put(from_key.as_str(), self.request.from.clone())?
-            .execute()
-            .await?;
-        kv.put(precision_key.as_str(), self.request.precision)?
-            .execute()
-            .await?;
-        kv.put(
-            timeseries_key.as_str(),
-            format!(
-                "{}_{}",
-                self.request.dates.start_date.clone(),
-                self.request.dates.end_date.clone()
-            ),
-        )?
-        .execute()
-        .await?;
+        Ok(preferences)
+    }
 
-        Ok(())
+    /// Ranges that ended before today are final and can be kept for much longer
+    /// without ever being refreshed.
+    fn timeseries_policy(&self) -> CachePolicy {
+        let today = chrono::Utc::today()
+            .naive_utc()
+            .format("%Y-%m-%d")
+            .to_string();
+        // Either date may come first, ISO dates compare correctly as strings
+        let last = std::cmp::max(&self.request.dates.start_date, &self.request.dates.end_date);
+
+        if last < &today {
+            CachePolicy::new(self.cache_ttls.timeseries_past, 0)
+        } else {
+            CachePolicy::new(
+                self.cache_ttls.timeseries_current,
+                self.cache_ttls.timeseries_current,
+            )
+        }
+    }
+
+    /// The requested range relative to today, so it can be reused as a default later on.
+    fn timeseries_offset(&self) -> Option<TimeseriesOffset> {
+        let today = chrono::Utc::today().naive_utc();
+        let days_from_today = |date: &str| {
+            chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                .ok()
+                .map(|date| (date - today).num_days())
+        };
+
+        Some(TimeseriesOffset {
+            start: days_from_today(&self.request.dates.start_date)?,
+            end: days_from_today(&self.request.dates.end_date)?,
+        })
     }
 
     fn get_xe(&self) -> String {
@@ -407,7 +415,7 @@ impl XEClient {
             thumbnail: None,
             footer: None,
             fields: vec![],
-            color: Some(0xfdc835),
+            color: Some(DEFAULT_COLOR),
         }
     }
 
@@ -462,7 +470,7 @@ impl XEClient {
                     value: format!("{} -> {}", self.request.from, self.request.to),
                 },
             ],
-            color: Some(0xfdc835),
+            color: Some(DEFAULT_COLOR),
         }
     }
 }
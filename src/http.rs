@@ -1,20 +1,73 @@
-
 use serde::Serialize;
 use std::fmt;
 
 use crate::error::Error;
+use crate::interaction::{InteractionResponse, ResponseFile};
+use crate::logger;
 
-
-#[derive(Serialize)]
+/// A response independent of the server sending it.
 pub(crate) struct HttpResponse {
     pub status: u16,
-    pub body: String,
+    pub content_type: String,
+    pub body: Vec<u8>,
 }
 
-#[derive(Debug)]
+impl HttpResponse {
+    pub(crate) fn json<T: Serialize>(status: u16, value: &T) -> Self {
+        match serde_json::to_vec(value) {
+            Ok(body) => HttpResponse {
+                status,
+                content_type: "application/json".into(),
+                body,
+            },
+            Err(err) => HttpResponse::text(500, &err.to_string()),
+        }
+    }
+
+    pub(crate) fn text(status: u16, text: &str) -> Self {
+        HttpResponse {
+            status,
+            content_type: "text/plain; charset=utf-8".into(),
+            body: text.as_bytes().to_vec(),
+        }
+    }
+
+    /// The response to an interaction, sent as multipart when it comes with files.
+    pub(crate) fn interaction(result: Result<InteractionResponse, HttpError>) -> Self {
+        let result = match result {
+            Ok(result) => result,
+            Err(httperr) => {
+                log_warn!("Error response : {}", httperr);
+                let reason = httperr.to_string();
+                return HttpResponse::text(httperr.status as u16, &reason);
+            }
+        };
+
+        let payload = match serde_json::to_value(&result) {
+            Ok(payload) => payload,
+            Err(err) => return HttpResponse::text(500, &err.to_string()),
+        };
+        log_debug!("Response : {}", logger::redact_json(&payload));
+        if result.files.is_empty() {
+            return HttpResponse::json(200, &payload);
+        }
+
+        let (content_type, body) = multipart_body(&payload.to_string(), &result.files);
+        HttpResponse {
+            status: 200,
+            content_type,
+            body,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
 pub(crate) enum HttpStatus {
     BadRequest = 400,
     Unauthorized = 401,
+    MethodNotAllowed = 405,
+    PayloadTooLarge = 413,
+    UnsupportedMediaType = 415,
     InternalServerError = 500,
 }
 
@@ -24,6 +77,17 @@ pub struct HttpError {
     reason: Error,
 }
 
+impl HttpError {
+    pub(crate) fn kind(&self) -> &'static str {
+        self.reason.kind()
+    }
+
+    /// Every error the failure was wrapped in, which `Display` leaves out.
+    pub(crate) fn context(&self) -> String {
+        format!("{:?}", self.reason)
+    }
+}
+
 impl fmt::Display for HttpError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "An HTTP error occurred: {}", self.reason.to_string())
@@ -34,13 +98,48 @@ impl From<Error> for HttpError {
     fn from(error: Error) -> HttpError {
         HttpError {
             status: match &error {
-                Error::HeaderNotFound(_) | Error::JsonFailed(_) | Error::InvalidPayload(_) => {
-                    HttpStatus::BadRequest
-                }
+                Error::HeaderNotFound(_)
+                | Error::InvalidHeader(_, _)
+                | Error::JsonFailed(_)
+                | Error::InvalidPayload(_) => HttpStatus::BadRequest,
+                Error::MethodNotAllowed(_) => HttpStatus::MethodNotAllowed,
+                Error::PayloadTooLarge(_, _) => HttpStatus::PayloadTooLarge,
+                Error::UnsupportedContentType(_) => HttpStatus::UnsupportedMediaType,
                 Error::VerificationFailed(_) => HttpStatus::Unauthorized,
                 _ => HttpStatus::InternalServerError,
             },
             reason: error,
         }
     }
-}
\ No newline at end of file
+}
+
+/// Encodes a JSON payload and files the way Discord expects uploads, returning
+/// the content type and the body.
+pub(crate) fn multipart_body(payload_json: &str, files: &[ResponseFile]) -> (String, Vec<u8>) {
+    let boundary = format!("nuko-{:x}", chrono::Utc::now().timestamp_nanos());
+    let mut body = Vec::new();
+
+    body.extend_from_slice(
+        format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"payload_json\"\r\nContent-Type: application/json\r\n\r\n{}\r\n",
+            boundary, payload_json
+        )
+        .as_bytes(),
+    );
+    for (index, file) in files.iter().enumerate() {
+        body.extend_from_slice(
+            format!(
+                "--{}\r\nContent-Disposition: form-data; name=\"files[{}]\"; filename=\"{}\"\r\nContent-Type: application/octet-stream\r\n\r\n",
+                boundary,
+                index,
+                file.filename.replace('"', "")
+            )
+            .as_bytes(),
+        );
+        body.extend_from_slice(&file.content);
+        body.extend_from_slice(b"\r\n");
+    }
+    body.extend_from_slice(format!("--{}--\r\n", boundary).as_bytes());
+
+    (format!("multipart/form-data; boundary={}", boundary), body)
+}
@@ -1,9 +1,13 @@
 use serde::{Deserialize, Serialize};
 use serde_repr::{Deserialize_repr, Serialize_repr};
 
+use crate::analytics;
 use crate::command::init_commands;
-use crate::embed::Embed;
+use crate::context::CommandContext;
+use crate::embed::{Embed, DEFAULT_COLOR};
 use crate::error::{Error, InteractionError};
+use crate::guild_config::GuildConfig;
+use crate::locale::Text;
 
 #[derive(Deserialize_repr, Serialize)]
 #[repr(u8)]
@@ -48,6 +52,31 @@ pub(crate) struct InteractionApplicationCommandCallbackData {
     pub(crate) content: Option<String>,
     pub(crate) choices: Option<Vec<ApplicationCommandOptionChoice>>,
     pub(crate) embeds: Option<Vec<Embed>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) flags: Option<u64>,
+}
+
+/// How an interaction went, as logged once it has been answered.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Outcome {
+    Answered,
+    /// The command is disabled in the guild it was used in.
+    Disabled,
+    /// An upstream could not be reached, so a notice was sent instead.
+    UpstreamUnavailable,
+    /// The request was turned away before reaching a command.
+    Rejected,
+    Failed,
+}
+
+/// Only shows the response to the user who ran the command.
+pub(crate) const EPHEMERAL: u64 = 1 << 6;
+
+/// A file sent along with a response, as `files[n]` of a multipart body.
+pub(crate) struct ResponseFile {
+    pub(crate) filename: String,
+    pub(crate) content: Vec<u8>,
 }
 
 #[derive(Deserialize, Serialize)]
@@ -79,7 +108,7 @@ pub(crate) struct Member {
     pub deaf: bool,
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, Clone)]
 pub(crate) struct User {
     pub avatar: String,
     pub avatar_decoration: Option<String>,
@@ -118,6 +147,48 @@ pub(crate) struct ApplicationCommandOptionChoice {
 }
 
 impl Interaction {
+    pub(crate) fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    pub(crate) fn token(&self) -> &str {
+        &self.token
+    }
+
+    /// The type of interaction, as logged.
+    pub(crate) fn kind(&self) -> &'static str {
+        match self.ty {
+            InteractionType::Ping => "ping",
+            InteractionType::ApplicationCommand => "command",
+            InteractionType::MessageComponent => "component",
+            InteractionType::ApplicationCommandAutoComplete => "autocomplete",
+            InteractionType::ModalSubmit => "modal",
+        }
+    }
+
+    /// The options given to the command as `name=value` pairs, if any.
+    pub(crate) fn options_summary(&self) -> Option<String> {
+        let options = self.data.as_ref()?.options.as_ref()?;
+        Some(
+            options
+                .iter()
+                .map(|option| {
+                    format!(
+                        "{}={}",
+                        option.name,
+                        option.value.as_deref().unwrap_or_default()
+                    )
+                })
+                .collect::<Vec<String>>()
+                .join(" "),
+        )
+    }
+
+    /// The name of the command invoked, if any.
+    pub(crate) fn command_name(&self) -> Option<&str> {
+        self.data.as_ref().map(|data| data.name.as_str())
+    }
+
     fn data(&self) -> Result<&ApplicationCommandInteractionData, Error> {
         Ok(self
             .data
@@ -131,6 +202,8 @@ pub struct InteractionResponse {
     #[serde(rename = "type")]
     pub(crate) ty: InteractionResponseType,
     pub(crate) data: Option<InteractionApplicationCommandCallbackData>,
+    #[serde(skip)]
+    pub(crate) files: Vec<ResponseFile>,
 }
 
 impl Interaction {
@@ -138,24 +211,82 @@ impl Interaction {
         return InteractionResponse {
             ty: InteractionResponseType::Pong,
             data: None,
+            files: Vec::new(),
         };
     }
 
+    /// Makes the user who invoked the interaction, and the configuration of the
+    /// guild it came from, available to commands.
+    async fn load_invoker(&self, ctx: &mut CommandContext) -> Result<(), InteractionError> {
+        if let Some(user) = self
+            .member
+            .as_ref()
+            .map(|member| &member.user)
+            .or(self.user.as_ref())
+        {
+            ctx.set_user(user.clone());
+        }
+        if let Some(guild_id) = &self.guild_id {
+            let config = GuildConfig::load(ctx.store(), guild_id).await?;
+            ctx.set_guild(guild_id.clone(), config);
+        }
+        Ok(())
+    }
+
     pub(crate) async fn handle_command(
         &self,
-        ctx: &mut worker::RouteContext<()>,
+        ctx: &mut CommandContext,
     ) -> Result<InteractionResponse, InteractionError> {
         let data = self.data().map_err(|_| InteractionError::GenericError())?;
         let commands = init_commands();
+        self.load_invoker(ctx).await?;
 
         for boxed in commands.iter() {
             let com = &*boxed;
             if com.name() == data.name {
-                let response = com.respond(&self.member, &data.options, ctx).await?;
+                if !ctx.guild_config().is_enabled(&data.name) {
+                    ctx.set_outcome(Outcome::Disabled);
+                    return Ok(InteractionResponse {
+                        ty: InteractionResponseType::ChannelMessageWithSource,
+                        data: Some(InteractionApplicationCommandCallbackData {
+                            content: Some(ctx.language().text(Text::CommandDisabled).into()),
+                            choices: None,
+                            embeds: None,
+                            flags: Some(EPHEMERAL),
+                        }),
+                        files: Vec::new(),
+                    });
+                }
+
+                let mut response = match com.respond(&self.member, &data.options, ctx).await {
+                    Ok(response) => response,
+                    // Discord would only say the interaction failed, tell the user why instead
+                    Err(InteractionError::UpstreamError(err)) => {
+                        log_warn!("/{} could not reach upstream : {}", data.name, err);
+                        ctx.set_outcome(Outcome::UpstreamUnavailable);
+                        InteractionApplicationCommandCallbackData {
+                            content: Some(ctx.language().text(Text::UpstreamUnavailable).into()),
+                            choices: None,
+                            embeds: None,
+                            flags: Some(EPHEMERAL),
+                        }
+                    }
+                    Err(err) => return Err(err),
+                };
+                analytics::record_command(ctx, &data.name);
+                if let (Some(color), Some(embeds)) =
+                    (ctx.guild_config().embed_color, response.embeds.as_mut())
+                {
+                    embeds
+                        .iter_mut()
+                        .filter(|embed| embed.color == Some(DEFAULT_COLOR))
+                        .for_each(|embed| embed.color = Some(color));
+                }
 
                 return Ok(InteractionResponse {
                     ty: InteractionResponseType::ChannelMessageWithSource,
                     data: Some(response),
+                    files: ctx.take_files(),
                 });
             }
         }
@@ -164,19 +295,36 @@ impl Interaction {
 
     pub(crate) async fn handle_autocomplete(
         &self,
-        ctx: &mut worker::RouteContext<()>,
+        ctx: &mut CommandContext,
     ) -> Result<InteractionResponse, InteractionError> {
         let data = self.data().map_err(|_| InteractionError::GenericError())?;
         let commands = init_commands();
+        self.load_invoker(ctx).await?;
 
         for boxed in commands.iter() {
             let com = &*boxed;
             if com.name() == data.name {
+                // Nothing to suggest for a command that will not run anyway
+                if !ctx.guild_config().is_enabled(&data.name) {
+                    ctx.set_outcome(Outcome::Disabled);
+                    return Ok(InteractionResponse {
+                        ty: InteractionResponseType::AutoCompleteResult,
+                        data: Some(InteractionApplicationCommandCallbackData {
+                            content: None,
+                            choices: Some(Vec::new()),
+                            embeds: None,
+                            flags: None,
+                        }),
+                        files: Vec::new(),
+                    });
+                }
+
                 let response = com.autocomplete(&data.options, ctx).await?;
 
                 return Ok(InteractionResponse {
                     ty: InteractionResponseType::AutoCompleteResult,
                     data: Some(response),
+                    files: Vec::new(),
                 });
             }
         }
@@ -185,7 +333,7 @@ impl Interaction {
 
     pub(crate) async fn perform(
         &self,
-        ctx: &mut worker::RouteContext<()>,
+        ctx: &mut CommandContext,
     ) -> Result<InteractionResponse, Error> {
         match self.ty {
             InteractionType::Ping => Ok(self.handle_ping()),
@@ -0,0 +1,119 @@
+//! Work run on a schedule rather than in answer to an interaction.
+//!
+//! Jobs are listed in [`init_jobs`] and given cron expressions with
+//! `JOB_SCHEDULE`. When a cron trigger fires, every job scheduled with that
+//! exact expression runs, and how its last run went is kept under `job:` keys.
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::context::CommandContext;
+use crate::error::InteractionError;
+use crate::jobs;
+use crate::logger::{Event, Level};
+use crate::store::migration::{read_record, write_record, Record};
+use crate::store::{Store, StoreError};
+
+/// What a run triggered through `POST /jobs/{name}` is recorded as.
+pub(crate) const MANUAL_TRIGGER: &str = "manual";
+/// How much of an error is kept with a [`JobRun`].
+const MAX_ERROR_CHARS: usize = 300;
+
+#[async_trait(?Send)]
+pub(crate) trait Job {
+    /// The name the job is scheduled under, ie `register_commands`.
+    fn name(&self) -> &'static str;
+
+    async fn run(&self, ctx: &CommandContext) -> Result<(), InteractionError>;
+}
+
+pub(crate) fn init_jobs() -> Vec<Box<dyn Job + Sync>> {
+    vec![Box::new(jobs::register_commands::RegisterCommands {})]
+}
+
+pub(crate) fn find_job(name: &str) -> Option<Box<dyn Job + Sync>> {
+    init_jobs().into_iter().find(|job| job.name() == name)
+}
+
+/// How the last run of a job went.
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+pub(crate) struct JobRun {
+    /// The cron expression that triggered the run, or `manual`.
+    pub(crate) trigger: String,
+    /// Unix seconds the run started at.
+    pub(crate) started_at: i64,
+    pub(crate) duration_ms: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) error: Option<String>,
+}
+
+impl Record for JobRun {
+    const KIND: &'static str = "job run";
+    const PREFIX: &'static str = "job:";
+    const VERSION: u32 = 1;
+
+    fn upgrade(version: u32, _value: Value) -> Result<Value, StoreError> {
+        Err(StoreError::UnsupportedVersion(Self::KIND.into(), version))
+    }
+}
+
+fn job_key(name: &str) -> String {
+    format!("{}{}", JobRun::PREFIX, name)
+}
+
+impl JobRun {
+    /// The last run of the job called `name`, if it ever ran.
+    pub(crate) async fn load(store: &dyn Store, name: &str) -> Result<Option<Self>, StoreError> {
+        read_record(store, &job_key(name)).await
+    }
+
+    pub(crate) fn succeeded(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// Runs `job`, then logs and stores how it went.
+pub(crate) async fn run_job(ctx: &CommandContext, job: &dyn Job, trigger: &str) -> JobRun {
+    let started = chrono::Utc::now();
+    let result = job.run(ctx).await;
+    let run = JobRun {
+        trigger: trigger.into(),
+        started_at: started.timestamp(),
+        duration_ms: (chrono::Utc::now() - started).num_milliseconds(),
+        error: result
+            .err()
+            .map(|err| err.to_string().chars().take(MAX_ERROR_CHARS).collect()),
+    };
+
+    Event::new("job")
+        .with("job", job.name())
+        .with("trigger", &run.trigger)
+        .with("duration_ms", run.duration_ms)
+        .with("error", &run.error)
+        .emit(if run.succeeded() {
+            Level::Info
+        } else {
+            Level::Error
+        });
+    if let Err(err) = write_record(ctx.store(), &job_key(job.name()), &run, None).await {
+        log_warn!("Unable to record the run of {} : {}", job.name(), err);
+    }
+    run
+}
+
+/// Runs every job scheduled with `cron`, one after the other.
+pub(crate) async fn run_scheduled(ctx: &CommandContext, cron: &str) -> Vec<JobRun> {
+    let names = ctx.config().job_schedule.jobs_for(cron);
+    if names.is_empty() {
+        log_warn!("No jobs are scheduled for '{}'", cron);
+    }
+
+    let mut runs = Vec::new();
+    for name in names {
+        if let Some(job) = find_job(name) {
+            runs.push(run_job(ctx, job.as_ref(), cron).await);
+        }
+    }
+    runs
+}
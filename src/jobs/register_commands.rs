@@ -0,0 +1,22 @@
+use crate::command::register_commands;
+use crate::context::CommandContext;
+use crate::error::InteractionError;
+use crate::job::Job;
+
+use async_trait::async_trait;
+
+/// Registers the commands again, so servers the bot joined since get theirs
+/// when `GUILD_COMMANDS` is set.
+pub(crate) struct RegisterCommands {}
+
+#[async_trait(?Send)]
+impl Job for RegisterCommands {
+    fn name(&self) -> &'static str {
+        "register_commands"
+    }
+
+    async fn run(&self, ctx: &CommandContext) -> Result<(), InteractionError> {
+        register_commands(ctx).await?;
+        Ok(())
+    }
+}
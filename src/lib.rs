@@ -1,29 +1,93 @@
+use std::rc::Rc;
+
 use worker::*;
 
+#[macro_use]
+mod logger;
+
+mod admin;
+mod analytics;
+mod audit;
 mod bot;
+mod cache;
+mod circuit_breaker;
+#[cfg(feature = "native")]
+pub mod cli;
 mod command;
 mod commands;
+mod config;
+mod context;
+mod diagnostics;
+mod discord;
 mod embed;
 mod error;
+mod error_report;
+mod guard;
+mod guild_config;
 mod helpers;
 mod http;
 mod interaction;
+mod job;
+mod jobs;
+mod locale;
+#[cfg(feature = "native")]
+pub mod native;
+mod preferences;
+mod routes;
+mod store;
+#[cfg(test)]
+mod tests;
+mod upstream;
+mod user_data;
 mod utils;
 mod verification;
 
+/// Starts logging as configured, or with the defaults when the configuration
+/// could not be loaded.
+fn init_logger(config: Option<&config::Config>) {
+    match config {
+        Some(config) => logger::init(config.log_level, config.log_format, config.secrets()),
+        None => logger::init(logger::Level::Info, logger::Format::Json, Vec::new()),
+    }
+}
+
+/// Hands a Workers request to the runtime-independent handlers.
+fn incoming_request(mut req: Request) -> Result<bot::IncomingRequest> {
+    let method = req.method().to_string();
+    let headers = req.headers().entries().collect();
+    Ok(bot::IncomingRequest::new(&method, headers, async move {
+        req.bytes().await.map_err(|err| err.to_string())
+    }))
+}
+
+fn into_response(response: http::HttpResponse) -> Result<Response> {
+    let mut headers = Headers::new();
+    headers.set("content-type", &response.content_type)?;
+    Ok(Response::from_bytes(response.body)?
+        .with_status(response.status)
+        .with_headers(headers))
+}
+
 fn log_request(req: &Request) {
-    console_log!(
-        "{} - [{}], located at: {:?}, within: {}",
-        Date::now().to_string(),
-        req.path(),
-        req.cf().coordinates().unwrap_or_default(),
-        req.cf().region().unwrap_or("unknown region".into())
-    );
+    logger::Event::new("request")
+        .with("path", req.path())
+        .with("coordinates", req.cf().coordinates().unwrap_or_default())
+        .with("region", req.cf().region())
+        .emit(logger::Level::Debug);
 }
 
 #[event(fetch)]
-pub async fn main(req: Request, env: Env, _ctx: worker::Context) -> Result<Response> {
+pub async fn main(req: Request, env: Env, worker_ctx: worker::Context) -> Result<Response> {
+    let config = config::Config::from_env(&env);
+    init_logger(config.as_ref().ok());
     log_request(&req);
+    let config = match config {
+        Ok(config) => Rc::new(config),
+        Err(err) => {
+            log_error!("{}", err);
+            return Response::error(err.to_string(), 500);
+        }
+    };
 
     // Optionally, get more helpful error messages written to the console in the case of a panic.
     utils::set_panic_hook();
@@ -31,68 +95,83 @@ pub async fn main(req: Request, env: Env, _ctx: worker::Context) -> Result<Respo
     // Optionally, use the Router to handle matching endpoints, use ":name" placeholders, or "*name"
     // catch-alls to match on specific patterns. Alternatively, use `Router::with_data(D)` to
     // provide arbitrary data that will be accessible in each route via the `ctx.data()` method.
-    let router = Router::new();
+    // Background work, such as cache refreshes, is queued on `ctx.data` and finished
+    // after the response has been sent.
+    let background = context::Background::default();
+    let router = Router::with_data(context::RouteData {
+        background: background.clone(),
+        config,
+    });
 
     // Add as many routes as your Worker needs! Each route will get a `Request` for handling HTTP
     // functionality and a `RouteContext` which you can use to  and get route parameters and
     // Environment bindings like KV Stores, Durable Objects, Secrets, and Variables.
-    router
+    let response = router
         .post_async("/", |req, ctx| async move {
-            let mut app = bot::App::new(req, ctx);
-
-            match app.handle_request().await {
-                Ok(result) => {
-                    worker::console_log!(
-                        "Response : {}",
-                        serde_json::to_string_pretty(&result).unwrap()
-                    );
-                    return Response::from_json(&result);
-                }
-                Err(httperr) => {
-                    worker::console_log!("Error response : {}", httperr.to_string());
-                    return Response::error(httperr.to_string(), httperr.status as u16);
-                }
-            };
+            let command_ctx = context::CommandContext::from_route(&ctx)?;
+            into_response(routes::interactions(incoming_request(req)?, command_ctx).await)
         })
         .post_async("/register", |_, ctx| async move {
-            let commands = command::init_commands();
-
-            let mut to_register: Vec<command::RegisteredCommand> = Vec::new();
-            for boxed in commands.iter() {
-                let com = &*boxed;
-                let reg = command::RegisteredCommand {
-                    name: com.name(),
-                    description: com.description(),
-                    options: com.options(),
-                };
-                to_register.push(reg);
-            }
-
-            let client = reqwest::Client::new();
-            let app_id = ctx.var("DISCORD_APPLICATION_ID")?.to_string();
-            let token = ctx.var("DISCORD_TOKEN")?.to_string();
-            let url = format!(
-                "https://discord.com/api/v10/applications/{}/commands",
-                app_id
-            );
-
-            let serialized = serde_json::to_string(&to_register)?;
-            worker::console_log! {"Sending  : {}", serialized};
-
-            let response = client
-                .put(url)
-                .body(serialized)
-                .header("Authorization", format!("Bot {}", token))
-                .header("Content-Type", "application/json")
-                .send()
-                .await
-                .unwrap()
-                .text()
-                .await
-                .unwrap();
-            worker::console_log! {"Registration response: {}", response};
-            return Response::ok(&response);
+            let command_ctx = context::CommandContext::from_route(&ctx)?;
+            into_response(routes::register(command_ctx).await)
+        })
+        .get_async("/health", |_, ctx| async move {
+            let command_ctx = context::CommandContext::from_route(&ctx)?;
+            into_response(routes::health(command_ctx).await)
+        })
+        .get("/version", |_, _| into_response(routes::version()))
+        .get_async("/diagnostics", |req, ctx| async move {
+            let command_ctx = context::CommandContext::from_route(&ctx)?;
+            let authorization = req.headers().get("authorization")?;
+            into_response(routes::diagnostics(command_ctx, authorization.as_deref()).await)
+        })
+        .post_async("/jobs/:name", |req, ctx| async move {
+            let command_ctx = context::CommandContext::from_route(&ctx)?;
+            let name = ctx.param("name").cloned().unwrap_or_default();
+            let authorization = req.headers().get("authorization")?;
+            into_response(routes::run_job(command_ctx, &name, authorization.as_deref()).await)
+        })
+        .post_async("/admin/migrate", |req, ctx| async move {
+            admin::migrate_records(req, ctx).await
+        })
+        .get_async("/admin/stats", |req, ctx| async move {
+            admin::usage_stats(req, ctx).await
         })
         .run(req, env)
-        .await
+        .await;
+
+    worker_ctx.wait_until(background.run());
+
+    response
+}
+
+#[event(scheduled)]
+pub async fn scheduled(event: ScheduledEvent, env: Env, worker_ctx: ScheduleContext) {
+    let config = match config::Config::from_env(&env) {
+        Ok(config) => config,
+        Err(err) => {
+            init_logger(None);
+            log_error!("{}", err);
+            return;
+        }
+    };
+    init_logger(Some(&config));
+    utils::set_panic_hook();
+
+    let kv = match env.kv(store::NAMESPACE) {
+        Ok(kv) => kv,
+        Err(err) => {
+            log_error!("Unable to open the store : {}", err);
+            return;
+        }
+    };
+    let background = context::Background::default();
+    let ctx = context::CommandContext::new(
+        Rc::new(store::WorkersKvStore::new(kv)),
+        Rc::new(config),
+        background.clone(),
+    );
+    job::run_scheduled(&ctx, &event.cron()).await;
+
+    worker_ctx.wait_until(background.run());
 }
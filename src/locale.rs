@@ -0,0 +1,133 @@
+//! The few fixed phrases the bot writes itself, in each supported language.
+
+use serde::{Deserialize, Serialize};
+use strum_macros::{Display, EnumIter, EnumString};
+
+#[derive(
+    Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Default, Display, EnumIter, EnumString,
+)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub(crate) enum Language {
+    #[default]
+    En,
+    Ja,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Text {
+    DefaultsUpdated,
+    CommandDisabled,
+    GuildOnly,
+    ManageGuildRequired,
+    AdministratorRequired,
+    ConfigTitle,
+    ConfigHelp,
+    ConfigUpdated,
+    InvalidValue,
+    NotSet,
+    MyDataTitle,
+    MyDataHelp,
+    NoData,
+    DataExported,
+    DataDeleted,
+    AuditTitle,
+    AuditHelp,
+    NoAuditEntries,
+    UpstreamUnavailable,
+    StatsTitle,
+    TopCommands,
+    TopPairs,
+    CacheHitRate,
+}
+
+impl Language {
+    pub(crate) fn text(self, text: Text) -> &'static str {
+        match (self, text) {
+            (Language::En, Text::DefaultsUpdated) => "Defaults have been updated",
+            (Language::En, Text::CommandDisabled) => "This command is disabled in this server",
+            (Language::En, Text::GuildOnly) => "This command can only be used in a server",
+            (Language::En, Text::ManageGuildRequired) => {
+                "You need the Manage Server permission to use this command"
+            }
+            (Language::En, Text::AdministratorRequired) => {
+                "You need the Administrator permission to use this command"
+            }
+            (Language::En, Text::ConfigTitle) => "Server Configuration",
+            (Language::En, Text::ConfigHelp) => {
+                "Use /config with a setting and a value to change it"
+            }
+            (Language::En, Text::ConfigUpdated) => "Server configuration has been updated",
+            (Language::En, Text::InvalidValue) => "Invalid value",
+            (Language::En, Text::NotSet) => "Not set",
+            (Language::En, Text::MyDataTitle) => "Your Data",
+            (Language::En, Text::MyDataHelp) => {
+                "Use the export action to download it, or delete to remove it"
+            }
+            (Language::En, Text::NoData) => "Nothing is stored about you",
+            (Language::En, Text::DataExported) => "Everything stored about you is attached",
+            (Language::En, Text::DataDeleted) => "Everything stored about you has been deleted",
+            (Language::En, Text::AuditTitle) => "Audit Log",
+            (Language::En, Text::AuditHelp) => "Most recent changes first",
+            (Language::En, Text::NoAuditEntries) => "No configuration changes have been made yet",
+            (Language::En, Text::UpstreamUnavailable) => {
+                "This service is not responding right now, please try again in a minute"
+            }
+            (Language::En, Text::StatsTitle) => "Usage",
+            (Language::En, Text::TopCommands) => "Top commands",
+            (Language::En, Text::TopPairs) => "Top pairs",
+            (Language::En, Text::CacheHitRate) => "Cache hit rate",
+            (Language::Ja, Text::DefaultsUpdated) => "デフォルトを更新しました",
+            (Language::Ja, Text::CommandDisabled) => {
+                "このコマンドはこのサーバーで無効になっています"
+            }
+            (Language::Ja, Text::GuildOnly) => "このコマンドはサーバー内でのみ使用できます",
+            (Language::Ja, Text::ManageGuildRequired) => {
+                "このコマンドを使うにはサーバー管理権限が必要です"
+            }
+            (Language::Ja, Text::AdministratorRequired) => {
+                "このコマンドを使うには管理者権限が必要です"
+            }
+            (Language::Ja, Text::ConfigTitle) => "サーバー設定",
+            (Language::Ja, Text::ConfigHelp) => "/config に設定と値を指定して変更できます",
+            (Language::Ja, Text::ConfigUpdated) => "サーバー設定を更新しました",
+            (Language::Ja, Text::InvalidValue) => "無効な値です",
+            (Language::Ja, Text::NotSet) => "未設定",
+            (Language::Ja, Text::MyDataTitle) => "あなたのデータ",
+            (Language::Ja, Text::MyDataHelp) => "export で書き出し、delete で削除できます",
+            (Language::Ja, Text::NoData) => "あなたについて保存されているデータはありません",
+            (Language::Ja, Text::DataExported) => "保存されているデータをすべて添付しました",
+            (Language::Ja, Text::DataDeleted) => "保存されていたデータをすべて削除しました",
+            (Language::Ja, Text::AuditTitle) => "監査ログ",
+            (Language::Ja, Text::AuditHelp) => "新しい順に表示しています",
+            (Language::Ja, Text::NoAuditEntries) => "設定はまだ変更されていません",
+            (Language::Ja, Text::UpstreamUnavailable) => {
+                "現在サービスが応答していません。しばらくしてから再度お試しください"
+            }
+            (Language::Ja, Text::StatsTitle) => "利用状況",
+            (Language::Ja, Text::TopCommands) => "よく使われるコマンド",
+            (Language::Ja, Text::TopPairs) => "よく使われる通貨ペア",
+            (Language::Ja, Text::CacheHitRate) => "キャッシュヒット率",
+        }
+    }
+
+    pub(crate) fn stats_summary(self, days: i64, commands: u64, servers: usize) -> String {
+        match self {
+            Language::En => format!(
+                "Last {} days, {} commands in {} servers",
+                days, commands, servers
+            ),
+            Language::Ja => format!(
+                "過去{}日間、{}サーバーで{}回のコマンド",
+                days, servers, commands
+            ),
+        }
+    }
+
+    pub(crate) fn hit_rate(self, rate: f64, lookups: u32) -> String {
+        match self {
+            Language::En => format!("{:.1}% of {} lookups", rate, lookups),
+            Language::Ja => format!("{}回中 {:.1}%", lookups, rate),
+        }
+    }
+}
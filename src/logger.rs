@@ -0,0 +1,317 @@
+//! Logging facade that scrubs credentials and user identifiers before anything
+//! reaches the console.
+//!
+//! The level is read from `LOG_LEVEL` (`error`, `warn`, `info` or `debug`,
+//! defaulting to `info`). Use the `log_error!`, `log_warn!`, `log_info!` and
+//! `log_debug!` macros rather than `console_log!` directly, and an [`Event`]
+//! for anything worth querying later.
+//!
+//! Every line is a JSON object with `time`, `level` and `event` keys, which
+//! Workers Logpush and `wrangler tail` pass on as is. `LOG_FORMAT=text` writes
+//! plain lines instead, for reading logs in a terminal.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::str::FromStr;
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+const REDACTED: &str = "[redacted]";
+
+/// Keys whose values are credentials wherever they appear.
+const SECRET_KEYS: [&str; 5] = ["token", "apikey", "api_key", "authorization", "password"];
+/// Keys whose values identify a user wherever they appear.
+const USER_KEYS: [&str; 7] = [
+    "user_id",
+    "username",
+    "global_name",
+    "nick",
+    "discriminator",
+    "avatar",
+    "email",
+];
+/// Objects whose `id` is a user's ID.
+const USER_OBJECTS: [&str; 3] = ["user", "member", "author"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Level {
+    Error,
+    Warn,
+    Info,
+    Debug,
+}
+
+impl FromStr for Level {
+    type Err = String;
+
+    fn from_str(level: &str) -> Result<Self, Self::Err> {
+        match level.trim().to_ascii_lowercase().as_str() {
+            "error" => Ok(Level::Error),
+            "warn" | "warning" => Ok(Level::Warn),
+            "info" => Ok(Level::Info),
+            "debug" => Ok(Level::Debug),
+            other => Err(format!("unknown log level '{}'", other)),
+        }
+    }
+}
+
+impl Level {
+    fn name(self) -> &'static str {
+        match self {
+            Level::Error => "error",
+            Level::Warn => "warn",
+            Level::Info => "info",
+            Level::Debug => "debug",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Format {
+    Json,
+    Text,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format.trim().to_ascii_lowercase().as_str() {
+            "json" => Ok(Format::Json),
+            "text" => Ok(Format::Text),
+            other => Err(format!("unknown log format '{}'", other)),
+        }
+    }
+}
+
+/// Where log lines end up once rendered and scrubbed.
+pub(crate) trait Sink {
+    fn write(&self, level: Level, line: &str);
+}
+
+/// The Workers console, or standard error when running natively.
+struct Console;
+
+impl Sink for Console {
+    fn write(&self, level: Level, line: &str) {
+        emit(level, line);
+    }
+}
+
+struct LoggerState {
+    level: Level,
+    format: Format,
+    secrets: Vec<String>,
+    sink: Option<Rc<dyn Sink>>,
+}
+
+thread_local! {
+    static STATE: RefCell<LoggerState> = const {
+        RefCell::new(LoggerState {
+            level: Level::Info,
+            format: Format::Json,
+            secrets: Vec::new(),
+            sink: None,
+        })
+    };
+}
+
+/// Resets the logger for a new request, `secrets` are scrubbed from every message.
+pub(crate) fn init(level: Level, format: Format, secrets: Vec<String>) {
+    STATE.with(|state| {
+        let mut state = state.borrow_mut();
+        state.level = level;
+        state.format = format;
+        state.secrets = secrets.into_iter().filter(|s| !s.is_empty()).collect();
+    });
+}
+
+/// Sends lines to `sink` rather than the console, until reset with `None`.
+#[cfg(test)]
+pub(crate) fn set_sink(sink: Option<Rc<dyn Sink>>) {
+    STATE.with(|state| state.borrow_mut().sink = sink);
+}
+
+/// Adds a value only known mid-request, such as an interaction token, to the
+/// values scrubbed from messages.
+pub(crate) fn add_secret(secret: &str) {
+    if secret.is_empty() {
+        return;
+    }
+    STATE.with(|state| state.borrow_mut().secrets.push(secret.to_string()));
+}
+
+pub(crate) fn enabled(level: Level) -> bool {
+    STATE.with(|state| level <= state.borrow().level)
+}
+
+/// Writes a message at `level`, use the `log_*!` macros instead.
+pub(crate) fn write(level: Level, message: &str) {
+    Event::new("log").with("message", message).emit(level);
+}
+
+/// A named log line with fields, written as a single JSON object.
+pub(crate) struct Event {
+    name: &'static str,
+    fields: Map<String, Value>,
+}
+
+impl Event {
+    pub(crate) fn new(name: &'static str) -> Self {
+        Event {
+            name,
+            fields: Map::new(),
+        }
+    }
+
+    /// Sets `key`, values that fail to serialize are left out.
+    pub(crate) fn insert<T: Serialize>(&mut self, key: &str, value: T) {
+        if let Ok(value) = serde_json::to_value(value) {
+            self.fields.insert(key.to_string(), value);
+        }
+    }
+
+    pub(crate) fn with<T: Serialize>(mut self, key: &str, value: T) -> Self {
+        self.insert(key, value);
+        self
+    }
+
+    /// Writes the event if `level` is enabled, redacting it like any payload.
+    pub(crate) fn emit(self, level: Level) {
+        if !enabled(level) {
+            return;
+        }
+        let fields = match redact_json(&Value::Object(self.fields)) {
+            Value::Object(fields) => fields,
+            _ => Map::new(),
+        };
+        let (format, sink) = STATE.with(|state| {
+            let state = state.borrow();
+            (state.format, state.sink.clone())
+        });
+        let line = redact(&render(format, level, self.name, fields));
+        match sink {
+            Some(sink) => sink.write(level, &line),
+            None => Console.write(level, &line),
+        }
+    }
+}
+
+fn render(format: Format, level: Level, name: &str, fields: Map<String, Value>) -> String {
+    let time = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true);
+    match format {
+        Format::Json => {
+            let mut line = Map::new();
+            line.insert("time".into(), time.into());
+            line.insert("level".into(), level.name().into());
+            line.insert("event".into(), name.into());
+            line.extend(fields);
+            Value::Object(line).to_string()
+        }
+        Format::Text => {
+            let mut line = format!("{} [{}]", time, level.name());
+            if name != "log" {
+                line.push(' ');
+                line.push_str(name);
+            }
+            for (key, value) in fields {
+                match value {
+                    Value::String(message) if key == "message" => {
+                        line.push(' ');
+                        line.push_str(&message);
+                    }
+                    value => line.push_str(&format!(" {}={}", key, value)),
+                }
+            }
+            line
+        }
+    }
+}
+
+/// Replaces every known secret in `text`.
+pub(crate) fn redact(text: &str) -> String {
+    STATE.with(|state| {
+        state
+            .borrow()
+            .secrets
+            .iter()
+            .fold(text.to_string(), |text, secret| {
+                text.replace(secret.as_str(), REDACTED)
+            })
+    })
+}
+
+/// Copies `value` with credentials and user identifiers replaced, for logging payloads.
+pub(crate) fn redact_json(value: &Value) -> Value {
+    redact_value(value, None)
+}
+
+fn redact_value(value: &Value, parent: Option<&str>) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(key, value)| {
+                    let lower = key.to_ascii_lowercase();
+                    let is_user_id =
+                        lower == "id" && parent.is_some_and(|p| USER_OBJECTS.contains(&p));
+                    let redacted = if SECRET_KEYS.contains(&lower.as_str())
+                        || USER_KEYS.contains(&lower.as_str())
+                        || is_user_id
+                    {
+                        Value::String(REDACTED.into())
+                    } else {
+                        redact_value(value, Some(lower.as_str()))
+                    };
+                    (key.clone(), redacted)
+                })
+                .collect(),
+        ),
+        Value::Array(values) => Value::Array(
+            values
+                .iter()
+                .map(|value| redact_value(value, parent))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn emit(level: Level, message: &str) {
+    match level {
+        Level::Error => worker::console_error!("{}", message),
+        Level::Warn => worker::console_warn!("{}", message),
+        Level::Info => worker::console_log!("{}", message),
+        Level::Debug => worker::console_debug!("{}", message),
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn emit(_level: Level, message: &str) {
+    eprintln!("{}", message);
+}
+
+macro_rules! log_at {
+    ($level:expr, $($arg:tt)+) => {
+        if $crate::logger::enabled($level) {
+            $crate::logger::write($level, &format!($($arg)+));
+        }
+    };
+}
+
+macro_rules! log_error {
+    ($($arg:tt)+) => { log_at!($crate::logger::Level::Error, $($arg)+) };
+}
+
+macro_rules! log_warn {
+    ($($arg:tt)+) => { log_at!($crate::logger::Level::Warn, $($arg)+) };
+}
+
+macro_rules! log_info {
+    ($($arg:tt)+) => { log_at!($crate::logger::Level::Info, $($arg)+) };
+}
+
+macro_rules! log_debug {
+    ($($arg:tt)+) => { log_at!($crate::logger::Level::Debug, $($arg)+) };
+}
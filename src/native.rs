@@ -0,0 +1,142 @@
+//! Serves the bot's endpoints from a native HTTP server, for running the bot
+//! on your own machine instead of Cloudflare Workers.
+//!
+//! Configuration is read from environment variables with the same names as
+//! the Workers vars and secrets, and checked once at startup. Data is kept in
+//! a JSON file.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use hyper::service::service_fn;
+use hyper::{Body, Method, Request, Response};
+use tokio::net::TcpListener;
+use tokio::task::{spawn_local, LocalSet};
+
+use crate::bot::IncomingRequest;
+use crate::config::Config;
+use crate::context::{Background, CommandContext};
+use crate::http::HttpResponse;
+use crate::routes;
+use crate::store::{FileStore, Store};
+
+const DEFAULT_BIND: &str = "127.0.0.1:8787";
+pub(crate) const DEFAULT_DATA_FILE: &str = "nuko-data.json";
+
+pub(crate) fn var(key: &str) -> Option<String> {
+    std::env::var(key).ok()
+}
+
+/// Runs hyper's connection tasks on the current thread, since command futures
+/// are not `Send`.
+#[derive(Clone, Copy)]
+struct LocalExec;
+
+impl<F> hyper::rt::Executor<F> for LocalExec
+where
+    F: Future + 'static,
+{
+    fn execute(&self, future: F) {
+        spawn_local(future);
+    }
+}
+
+/// Serves the bot on `NUKO_BIND` until the process is stopped, keeping data in
+/// `NUKO_DATA_FILE`.
+pub fn run() -> std::io::Result<()> {
+    let config = Config::load(var, var)
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, err.to_string()))?;
+    crate::init_logger(Some(&config));
+
+    let bind = var("NUKO_BIND")
+        .unwrap_or_else(|| DEFAULT_BIND.into())
+        .parse::<SocketAddr>()
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, err))?;
+    let data_file =
+        PathBuf::from(var("NUKO_DATA_FILE").unwrap_or_else(|| DEFAULT_DATA_FILE.into()));
+    let store: Rc<dyn Store> =
+        Rc::new(FileStore::open(data_file).map_err(|err| std::io::Error::other(err.to_string()))?);
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    LocalSet::new().block_on(&runtime, serve(bind, store, Rc::new(config)))
+}
+
+async fn serve(bind: SocketAddr, store: Rc<dyn Store>, config: Rc<Config>) -> std::io::Result<()> {
+    let listener = TcpListener::bind(bind).await?;
+    log_info!("Listening on {}", bind);
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let store = store.clone();
+        let config = config.clone();
+        spawn_local(async move {
+            let service = service_fn(move |req| handle(req, store.clone(), config.clone()));
+            if let Err(err) = hyper::server::conn::Http::new()
+                .with_executor(LocalExec)
+                .serve_connection(stream, service)
+                .await
+            {
+                log_warn!("Connection failed : {}", err);
+            }
+        });
+    }
+}
+
+async fn handle(
+    req: Request<Body>,
+    store: Rc<dyn Store>,
+    config: Rc<Config>,
+) -> Result<Response<Body>, std::convert::Infallible> {
+    let background = Background::default();
+    let ctx = CommandContext::new(store, config, background.clone());
+
+    let response = match (req.method(), req.uri().path()) {
+        (&Method::POST, "/") => routes::interactions(incoming_request(req), ctx).await,
+        (&Method::POST, "/register") => routes::register(ctx).await,
+        (&Method::GET, "/health") => routes::health(ctx).await,
+        (&Method::GET, "/version") => routes::version(),
+        (&Method::GET, "/diagnostics") => {
+            let authorization = req
+                .headers()
+                .get("authorization")
+                .and_then(|value| value.to_str().ok());
+            routes::diagnostics(ctx, authorization).await
+        }
+        (&Method::POST, path) if path.starts_with("/jobs/") => {
+            let authorization = req
+                .headers()
+                .get("authorization")
+                .and_then(|value| value.to_str().ok());
+            routes::run_job(ctx, &path["/jobs/".len()..], authorization).await
+        }
+        _ => HttpResponse::text(404, "Not Found"),
+    };
+    spawn_local(background.run());
+
+    Ok(Response::builder()
+        .status(response.status)
+        .header("content-type", response.content_type)
+        .body(Body::from(response.body))
+        .unwrap_or_else(|_| Response::new(Body::empty())))
+}
+
+fn incoming_request(req: Request<Body>) -> IncomingRequest {
+    let method = req.method().to_string();
+    let headers = req
+        .headers()
+        .iter()
+        .filter_map(|(key, value)| Some((key.to_string(), value.to_str().ok()?.to_string())))
+        .collect::<HashMap<String, String>>();
+
+    IncomingRequest::new(&method, headers, async move {
+        hyper::body::to_bytes(req.into_body())
+            .await
+            .map(|body| body.to_vec())
+            .map_err(|err| err.to_string())
+    })
+}
@@ -0,0 +1,147 @@
+//! Per-user settings, stored as a single versioned JSON record keyed by the
+//! user's Discord ID.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::guild_config::GuildConfig;
+use crate::store::migration::{read_record, write_record, Record};
+use crate::store::{Store, StoreError};
+use crate::user_data::UserData;
+
+/// Suffixes of the keys defaults used to be stored under, as `{username}:{suffix}`.
+const LEGACY_SUFFIXES: [&str; 4] = [
+    "currency_from",
+    "currency_to",
+    "currency_precision",
+    "timeseries_offset",
+];
+
+/// The default timeseries range, in days relative to the day it is requested.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+pub(crate) struct TimeseriesOffset {
+    pub(crate) start: i64,
+    pub(crate) end: i64,
+}
+
+impl TimeseriesOffset {
+    /// Parses the `{start}_{end}` format offsets used to be stored in.
+    fn parse_legacy(offset: &str) -> Option<Self> {
+        let mut split = offset.split('_');
+        Some(TimeseriesOffset {
+            start: split.next()?.parse().ok()?,
+            end: split.next()?.parse().ok()?,
+        })
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Default)]
+pub(crate) struct UserPreferences {
+    pub(crate) currency_from: Option<String>,
+    pub(crate) currency_to: Option<String>,
+    pub(crate) precision: Option<usize>,
+    pub(crate) timeseries_offset: Option<TimeseriesOffset>,
+}
+
+impl Record for UserPreferences {
+    const KIND: &'static str = "user preferences";
+    const PREFIX: &'static str = "user_preferences:";
+    const VERSION: u32 = 2;
+
+    fn upgrade(version: u32, mut value: Value) -> Result<Value, StoreError> {
+        match version {
+            // Offsets were stored as a `{start}_{end}` string
+            1 => {
+                let offset = value
+                    .get("timeseries_offset")
+                    .and_then(Value::as_str)
+                    .and_then(TimeseriesOffset::parse_legacy);
+                value["timeseries_offset"] = serde_json::to_value(offset)?;
+                Ok(value)
+            }
+            _ => Err(StoreError::UnsupportedVersion(Self::KIND.into(), version)),
+        }
+    }
+}
+
+fn user_preferences_key(user_id: &str) -> String {
+    UserData::Preferences.key(user_id)
+}
+
+impl UserPreferences {
+    /// Fills in anything the user has not set with the defaults of their guild.
+    pub(crate) fn with_guild_defaults(self, guild: &GuildConfig) -> Self {
+        UserPreferences {
+            currency_from: self.currency_from.or_else(|| guild.currency_from.clone()),
+            currency_to: self.currency_to.or_else(|| guild.currency_to.clone()),
+            precision: self.precision.or(guild.precision),
+            timeseries_offset: self.timeseries_offset,
+        }
+    }
+
+    /// Loads the preferences of `user_id`, falling back to the legacy keys of
+    /// `username` the first time a user is seen. Users without legacy keys get
+    /// an empty record, so they are only looked for once.
+    pub(crate) async fn load(
+        store: &dyn Store,
+        user_id: &str,
+        username: &str,
+    ) -> Result<Self, StoreError> {
+        if let Some(preferences) = read_record(store, &user_preferences_key(user_id)).await? {
+            return Ok(preferences);
+        }
+
+        match UserPreferences::migrate_legacy(store, user_id, username).await? {
+            Some(preferences) => Ok(preferences),
+            None => {
+                let preferences = UserPreferences::default();
+                preferences.save(store, user_id).await?;
+                Ok(preferences)
+            }
+        }
+    }
+
+    pub(crate) async fn save(&self, store: &dyn Store, user_id: &str) -> Result<(), StoreError> {
+        write_record(store, &user_preferences_key(user_id), self, None).await
+    }
+
+    /// Moves defaults stored under `{username}:*` keys into a record for `user_id`.
+    ///
+    /// The legacy keys are deleted afterwards, so they are only ever claimed once
+    /// and cannot be picked up by whoever takes over the username later.
+    async fn migrate_legacy(
+        store: &dyn Store,
+        user_id: &str,
+        username: &str,
+    ) -> Result<Option<Self>, StoreError> {
+        let mut legacy = Vec::new();
+        for suffix in LEGACY_SUFFIXES.iter() {
+            legacy.push(store.get(&format!("{}:{}", username, suffix)).await?);
+        }
+        if legacy.iter().all(Option::is_none) {
+            return Ok(None);
+        }
+
+        let mut legacy = legacy.into_iter();
+        let preferences = UserPreferences {
+            currency_from: legacy.next().flatten(),
+            currency_to: legacy.next().flatten(),
+            precision: legacy
+                .next()
+                .flatten()
+                .and_then(|precision| precision.parse::<usize>().ok()),
+            timeseries_offset: legacy
+                .next()
+                .flatten()
+                .and_then(|offset| TimeseriesOffset::parse_legacy(&offset)),
+        };
+        preferences.save(store, user_id).await?;
+
+        for suffix in LEGACY_SUFFIXES.iter() {
+            store.delete(&format!("{}:{}", username, suffix)).await?;
+        }
+        log_info!("Migrated legacy defaults to a preferences record");
+
+        Ok(Some(preferences))
+    }
+}
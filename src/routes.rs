@@ -0,0 +1,71 @@
+//! The bot's endpoints, independent of the server running them.
+
+use crate::admin::bearer_matches;
+use crate::bot::{App, IncomingRequest};
+use crate::command::register_commands;
+use crate::context::CommandContext;
+use crate::diagnostics::{Diagnostics, Health, Version};
+use crate::http::HttpResponse;
+use crate::job::{self, find_job, MANUAL_TRIGGER};
+
+/// `POST /`, where Discord sends interactions.
+pub(crate) async fn interactions(req: IncomingRequest, ctx: CommandContext) -> HttpResponse {
+    let mut app = App::new(req, ctx);
+    HttpResponse::interaction(app.handle_request().await)
+}
+
+/// `POST /register`, registers every command with Discord.
+pub(crate) async fn register(ctx: CommandContext) -> HttpResponse {
+    match register_commands(&ctx).await {
+        Ok(registered) => HttpResponse::json(200, &registered),
+        Err(err) => {
+            log_error!("Registration failed: {}", err);
+            HttpResponse::text(502, &err.to_string())
+        }
+    }
+}
+
+/// `GET /health`, whether the bot can reach its store.
+pub(crate) async fn health(ctx: CommandContext) -> HttpResponse {
+    let health = Health::check(&ctx).await;
+    HttpResponse::json(if health.is_ok() { 200 } else { 503 }, &health)
+}
+
+/// `GET /version`, the build and the commands it would register.
+pub(crate) fn version() -> HttpResponse {
+    HttpResponse::json(200, &Version::current())
+}
+
+/// `GET /diagnostics`, configuration and upstream state for operators holding
+/// the admin token.
+pub(crate) async fn diagnostics(ctx: CommandContext, authorization: Option<&str>) -> HttpResponse {
+    if !bearer_matches(ctx.config().admin_token.as_deref(), authorization) {
+        return HttpResponse::text(401, "Unauthorized");
+    }
+    match Diagnostics::collect(&ctx).await {
+        Ok(diagnostics) => HttpResponse::json(200, &diagnostics),
+        Err(err) => {
+            log_error!("Unable to collect diagnostics: {}", err);
+            HttpResponse::text(500, &err.to_string())
+        }
+    }
+}
+
+/// `POST /jobs/{name}`, runs a scheduled job right away, for operators holding
+/// the admin token.
+pub(crate) async fn run_job(
+    ctx: CommandContext,
+    name: &str,
+    authorization: Option<&str>,
+) -> HttpResponse {
+    if !bearer_matches(ctx.config().admin_token.as_deref(), authorization) {
+        return HttpResponse::text(401, "Unauthorized");
+    }
+    let job = match find_job(name) {
+        Some(job) => job,
+        None => return HttpResponse::text(404, "Not Found"),
+    };
+
+    let run = job::run_job(&ctx, job.as_ref(), MANUAL_TRIGGER).await;
+    HttpResponse::json(if run.succeeded() { 200 } else { 500 }, &run)
+}
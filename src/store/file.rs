@@ -0,0 +1,61 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+
+use super::memory::{Entry, MemoryStore};
+use super::{ListPage, Store, StoreError};
+
+/// A [`Store`] kept in memory and saved to a JSON file after every change, for
+/// self-hosting a single instance.
+pub(crate) struct FileStore {
+    path: PathBuf,
+    memory: MemoryStore,
+}
+
+impl FileStore {
+    /// Opens the store saved at `path`, starting empty if there is no file yet.
+    pub(crate) fn open(path: PathBuf) -> Result<Self, StoreError> {
+        let entries = match fs::read_to_string(&path) {
+            Ok(raw) => serde_json::from_str::<BTreeMap<String, Entry>>(&raw)?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(err) => return Err(StoreError::Backend(err.to_string())),
+        };
+
+        Ok(Self {
+            path,
+            memory: MemoryStore::from_entries(entries),
+        })
+    }
+
+    /// Writes to a temporary file first, so a crash never leaves a half-written store.
+    fn save(&self) -> Result<(), StoreError> {
+        let raw = serde_json::to_string(&*self.memory.entries().borrow())?;
+        let temporary = self.path.with_extension("tmp");
+        fs::write(&temporary, raw)
+            .and_then(|_| fs::rename(&temporary, &self.path))
+            .map_err(|err| StoreError::Backend(err.to_string()))
+    }
+}
+
+#[async_trait(?Send)]
+impl Store for FileStore {
+    async fn get(&self, key: &str) -> Result<Option<String>, StoreError> {
+        self.memory.get(key).await
+    }
+
+    async fn put(&self, key: &str, value: &str, ttl: Option<u64>) -> Result<(), StoreError> {
+        self.memory.put(key, value, ttl).await?;
+        self.save()
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StoreError> {
+        self.memory.delete(key).await?;
+        self.save()
+    }
+
+    async fn list(&self, prefix: &str, cursor: Option<String>) -> Result<ListPage, StoreError> {
+        self.memory.list(prefix, cursor).await
+    }
+}
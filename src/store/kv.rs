@@ -0,0 +1,57 @@
+use async_trait::async_trait;
+use worker::kv::{KvError, KvStore};
+
+use super::{ListPage, Store, StoreError};
+
+/// A [`Store`] backed by a Cloudflare KV namespace.
+pub(crate) struct WorkersKvStore {
+    kv: KvStore,
+}
+
+impl WorkersKvStore {
+    pub(crate) fn new(kv: KvStore) -> Self {
+        Self { kv }
+    }
+}
+
+impl From<KvError> for StoreError {
+    fn from(error: KvError) -> StoreError {
+        StoreError::Backend(error.to_string())
+    }
+}
+
+#[async_trait(?Send)]
+impl Store for WorkersKvStore {
+    async fn get(&self, key: &str) -> Result<Option<String>, StoreError> {
+        Ok(self.kv.get(key).text().await?)
+    }
+
+    async fn put(&self, key: &str, value: &str, ttl: Option<u64>) -> Result<(), StoreError> {
+        let mut put = self.kv.put(key, value)?;
+        if let Some(ttl) = ttl {
+            put = put.expiration_ttl(ttl);
+        }
+        Ok(put.execute().await?)
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StoreError> {
+        Ok(self.kv.delete(key).await?)
+    }
+
+    async fn list(&self, prefix: &str, cursor: Option<String>) -> Result<ListPage, StoreError> {
+        let mut list = self.kv.list().prefix(prefix.to_string());
+        if let Some(cursor) = cursor {
+            list = list.cursor(cursor);
+        }
+        let response = list.execute().await?;
+
+        Ok(ListPage {
+            keys: response.keys.into_iter().map(|key| key.name).collect(),
+            cursor: if response.list_complete {
+                None
+            } else {
+                response.cursor
+            },
+        })
+    }
+}
@@ -0,0 +1,98 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use super::{ListPage, Store, StoreError};
+
+/// Keys returned per [`Store::list`] page, matching Cloudflare KV.
+const PAGE_SIZE: usize = 1000;
+
+#[derive(Deserialize, Serialize)]
+pub(super) struct Entry {
+    value: String,
+    expires_at: Option<i64>,
+}
+
+/// A [`Store`] kept in memory, for running commands off the Workers runtime.
+#[allow(dead_code)]
+#[derive(Default)]
+pub(crate) struct MemoryStore {
+    entries: RefCell<BTreeMap<String, Entry>>,
+}
+
+#[allow(dead_code)]
+impl MemoryStore {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(super) fn from_entries(entries: BTreeMap<String, Entry>) -> Self {
+        Self {
+            entries: RefCell::new(entries),
+        }
+    }
+
+    pub(super) fn entries(&self) -> &RefCell<BTreeMap<String, Entry>> {
+        &self.entries
+    }
+
+    fn purge_expired(&self) {
+        let now = chrono::Utc::now().timestamp();
+        self.entries
+            .borrow_mut()
+            .retain(|_, entry| entry.expires_at.is_none_or(|expires_at| expires_at > now));
+    }
+}
+
+#[async_trait(?Send)]
+impl Store for MemoryStore {
+    async fn get(&self, key: &str) -> Result<Option<String>, StoreError> {
+        self.purge_expired();
+        Ok(self
+            .entries
+            .borrow()
+            .get(key)
+            .map(|entry| entry.value.clone()))
+    }
+
+    async fn put(&self, key: &str, value: &str, ttl: Option<u64>) -> Result<(), StoreError> {
+        let expires_at = ttl.map(|ttl| chrono::Utc::now().timestamp() + ttl as i64);
+        self.entries.borrow_mut().insert(
+            key.to_string(),
+            Entry {
+                value: value.to_string(),
+                expires_at,
+            },
+        );
+        Ok(())
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StoreError> {
+        self.entries.borrow_mut().remove(key);
+        Ok(())
+    }
+
+    async fn list(&self, prefix: &str, cursor: Option<String>) -> Result<ListPage, StoreError> {
+        self.purge_expired();
+        // The cursor is simply the last key of the previous page
+        let entries = self.entries.borrow();
+        let mut keys = entries
+            .keys()
+            .filter(|key| key.starts_with(prefix))
+            .filter(|key| cursor.as_ref().is_none_or(|cursor| *key > cursor))
+            .take(PAGE_SIZE + 1)
+            .cloned()
+            .collect::<Vec<String>>();
+
+        let cursor = if keys.len() > PAGE_SIZE {
+            keys.truncate(PAGE_SIZE);
+            keys.last().cloned()
+        } else {
+            None
+        };
+
+        Ok(ListPage { keys, cursor })
+    }
+}
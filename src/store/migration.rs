@@ -0,0 +1,159 @@
+//! Versioned records and the upgrades between their stored shapes.
+//!
+//! Every record is stored as JSON with a `version` field. Readers upgrade
+//! older shapes in memory as they load them, while [`migrate`] walks a whole
+//! key prefix and rewrites outdated records, for when old shapes should be
+//! dropped entirely.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+
+use super::{list_all, Store, StoreError};
+
+/// A type stored under its own key prefix.
+pub(crate) trait Record: Serialize + DeserializeOwned {
+    /// A short name for reports and errors.
+    const KIND: &'static str;
+    /// The prefix every key of this record type starts with.
+    const PREFIX: &'static str;
+    /// The current version, bump it and extend [`Record::upgrade`] when the shape changes.
+    const VERSION: u32;
+    /// Outdated records that can simply be thrown away, such as caches, are
+    /// deleted by [`migrate`] rather than rewritten.
+    const DISPOSABLE: bool = false;
+    /// Seconds a record is kept after it is written, for good if `None`. Writes
+    /// and [`migrate`] both use it, so records do not outlive their retention.
+    const TTL: Option<u64> = None;
+
+    /// Upgrades a stored value from `version` to `version + 1`.
+    ///
+    /// Values stored before records were versioned have version 0.
+    fn upgrade(version: u32, value: Value) -> Result<Value, StoreError>;
+}
+
+/// The version a stored value was written with.
+fn stored_version(value: &Value) -> u32 {
+    value
+        .get("version")
+        .and_then(Value::as_u64)
+        .map(|version| version as u32)
+        .unwrap_or(0)
+}
+
+/// Brings a stored value up to the current version of `R`.
+pub(crate) fn upgrade<R: Record>(mut value: Value) -> Result<R, StoreError> {
+    let mut version = stored_version(&value);
+    if version > R::VERSION {
+        return Err(StoreError::UnsupportedVersion(R::KIND.into(), version));
+    }
+
+    while version < R::VERSION {
+        value = R::upgrade(version, value)?;
+        version += 1;
+        if let Value::Object(map) = &mut value {
+            map.insert("version".into(), Value::from(version));
+        }
+    }
+
+    Ok(serde_json::from_value(value)?)
+}
+
+/// Reads the record under `key`, upgrading it to the current version if needed.
+pub(crate) async fn read_record<R: Record>(
+    store: &dyn Store,
+    key: &str,
+) -> Result<Option<R>, StoreError> {
+    match store.get(key).await? {
+        Some(raw) => Ok(Some(upgrade(serde_json::from_str(&raw)?)?)),
+        None => Ok(None),
+    }
+}
+
+/// Writes `record` under `key`, stamped with the current version.
+pub(crate) async fn write_record<R: Record>(
+    store: &dyn Store,
+    key: &str,
+    record: &R,
+    ttl: Option<u64>,
+) -> Result<(), StoreError> {
+    let mut value = serde_json::to_value(record)?;
+    if let Value::Object(map) = &mut value {
+        map.insert("version".into(), Value::from(R::VERSION));
+    }
+    store.put(key, &value.to_string(), ttl).await
+}
+
+#[derive(Serialize, Default)]
+pub(crate) struct MigrationReport {
+    pub(crate) kind: String,
+    pub(crate) scanned: usize,
+    pub(crate) upgraded: usize,
+    pub(crate) deleted: usize,
+    pub(crate) failed: Vec<String>,
+}
+
+/// Upgrades every stored record of type `R` to its current version.
+pub(crate) async fn migrate<R: Record>(store: &dyn Store) -> Result<MigrationReport, StoreError> {
+    let mut report = MigrationReport {
+        kind: R::KIND.into(),
+        ..MigrationReport::default()
+    };
+
+    for key in list_all(store, R::PREFIX).await? {
+        report.scanned += 1;
+
+        let value = match store.get(&key).await? {
+            Some(raw) => serde_json::from_str::<Value>(&raw),
+            // Expired or deleted since it was listed
+            None => continue,
+        };
+        let value = match value {
+            Ok(value) if stored_version(&value) == R::VERSION => continue,
+            Ok(value) => value,
+            Err(_) => {
+                report.failed.push(key);
+                continue;
+            }
+        };
+
+        if R::DISPOSABLE {
+            store.delete(&key).await?;
+            report.deleted += 1;
+            continue;
+        }
+
+        match upgrade::<R>(value) {
+            Ok(record) => {
+                write_record(store, &key, &record, R::TTL).await?;
+                report.upgraded += 1;
+            }
+            Err(err) => {
+                log_warn!("Unable to migrate {} : {}", key, err);
+                report.failed.push(key);
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+/// Deletes every key under a prefix that no record type uses anymore.
+pub(crate) async fn purge_retired(
+    store: &dyn Store,
+    kind: &str,
+    prefix: &str,
+) -> Result<MigrationReport, StoreError> {
+    let mut report = MigrationReport {
+        kind: kind.into(),
+        ..MigrationReport::default()
+    };
+
+    for key in list_all(store, prefix).await? {
+        report.scanned += 1;
+        store.delete(&key).await?;
+        report.deleted += 1;
+    }
+
+    Ok(report)
+}
@@ -0,0 +1,68 @@
+//! Key-value storage used by the commands, abstracting over Cloudflare KV so
+//! the logic built on top of it can also run off the Workers runtime.
+
+#[cfg(feature = "native")]
+mod file;
+mod kv;
+mod memory;
+pub(crate) mod migration;
+
+use async_trait::async_trait;
+
+#[cfg(feature = "native")]
+pub(crate) use file::FileStore;
+pub(crate) use kv::WorkersKvStore;
+#[allow(unused_imports)]
+pub(crate) use memory::MemoryStore;
+
+/// The KV namespace binding the bot keeps its data in.
+pub(crate) const NAMESPACE: &str = "exchange_defaults";
+
+/// The shortest expiration TTL Cloudflare KV accepts, in seconds.
+pub(crate) const MIN_TTL: u64 = 60;
+
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum StoreError {
+    #[error("Storage backend error: {0}")]
+    Backend(String),
+
+    #[error("Failed to deserialize from or serialize to JSON.")]
+    JsonFailed(#[from] serde_json::Error),
+
+    #[error("Stored {0} has unsupported version {1}.")]
+    UnsupportedVersion(String, u32),
+}
+
+/// One page of keys returned by [`Store::list`].
+pub(crate) struct ListPage {
+    pub(crate) keys: Vec<String>,
+    /// Pass back to [`Store::list`] to fetch the next page, `None` once exhausted.
+    pub(crate) cursor: Option<String>,
+}
+
+#[async_trait(?Send)]
+pub(crate) trait Store {
+    async fn get(&self, key: &str) -> Result<Option<String>, StoreError>;
+
+    /// Stores `value` under `key`, expiring it after `ttl` seconds if given.
+    async fn put(&self, key: &str, value: &str, ttl: Option<u64>) -> Result<(), StoreError>;
+
+    async fn delete(&self, key: &str) -> Result<(), StoreError>;
+
+    /// Lists keys starting with `prefix` in lexicographic order, a page at a time.
+    async fn list(&self, prefix: &str, cursor: Option<String>) -> Result<ListPage, StoreError>;
+}
+
+/// Collects every key starting with `prefix`, following the cursor across pages.
+pub(crate) async fn list_all(store: &dyn Store, prefix: &str) -> Result<Vec<String>, StoreError> {
+    let mut keys = Vec::new();
+    let mut cursor = None;
+    loop {
+        let page = store.list(prefix, cursor).await?;
+        keys.extend(page.keys);
+        match page.cursor {
+            Some(next) => cursor = Some(next),
+            None => return Ok(keys),
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use serde_json::Value;
+
+use crate::analytics::{self, DailyStats};
+
+use crate::locale::{Language, Text};
+
+use super::fixtures::{self, ADMINISTRATOR, MEMBER};
+use super::{json, run, Harness};
+
+fn field<'a>(embed: &'a Value, name: &str) -> &'a Value {
+    &embed["fields"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|field| field["name"] == name)
+        .unwrap_or_else(|| panic!("no {} field in {}", name, embed))["value"]
+}
+
+#[test]
+fn stats_add_up_the_commands_sent() {
+    run(async {
+        let harness = Harness::new();
+        harness
+            .jisho
+            .respond("GET", "/search/words", 200, fixtures::JISHO_NEKO);
+        harness
+            .fixer
+            .respond("GET", "/latest", 200, fixtures::FIXER_LATEST_AUD_JPY);
+        let commands = [
+            ("jisho", vec![("word", "neko")]),
+            ("jisho", vec![("word", "neko")]),
+            ("xe", vec![("from", "AUD"), ("to", "JPY")]),
+            ("xe", vec![("from", "AUD"), ("to", "JPY")]),
+            ("hey", vec![("name", "Loki")]),
+        ];
+        for (name, args) in commands.iter() {
+            harness
+                .send(&fixtures::guild_command(name, args, MEMBER))
+                .await;
+        }
+
+        let response = harness
+            .send(&fixtures::guild_command("stats", &[], MEMBER))
+            .await;
+
+        let embed = &json(&response)["data"]["embeds"][0];
+        assert_eq!(embed["description"], "Last 7 days, 5 commands in 1 servers");
+        assert_eq!(field(embed, "Top commands"), "jisho : 2\nxe : 2\nhey : 1");
+        assert_eq!(field(embed, "Top pairs"), "AUD_JPY : 2");
+        assert_eq!(field(embed, "Cache hit rate"), "50.0% of 4 lookups");
+        let mut total = DailyStats::default();
+        for stats in analytics::load_days(harness.store(), 2)
+            .await
+            .unwrap()
+            .values()
+        {
+            total.merge(stats);
+        }
+        assert_eq!(total.cache.len(), 2);
+        for counts in total.cache.values() {
+            assert_eq!((counts.hits, counts.stale, counts.misses), (1, 0, 1));
+        }
+    })
+}
+
+#[test]
+fn stats_are_shown_in_the_server_language() {
+    run(async {
+        let harness = Harness::new();
+        harness
+            .send(&fixtures::guild_command(
+                "config",
+                &[("setting", "language"), ("value", "ja")],
+                ADMINISTRATOR,
+            ))
+            .await;
+
+        let response = harness
+            .send(&fixtures::guild_command("stats", &[], MEMBER))
+            .await;
+
+        let embed = &json(&response)["data"]["embeds"][0];
+        assert_eq!(embed["title"], Language::Ja.text(Text::StatsTitle));
+        assert_eq!(embed["description"], Language::Ja.stats_summary(7, 1, 1));
+        assert_eq!(
+            field(embed, Language::Ja.text(Text::TopCommands)),
+            "config : 1"
+        );
+        assert_eq!(field(embed, Language::Ja.text(Text::CacheHitRate)), "-");
+    })
+}
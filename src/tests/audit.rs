@@ -0,0 +1,180 @@
+use serde_json::Value;
+
+use crate::audit::{self, guild_log_prefix};
+use crate::locale::{Language, Text};
+
+use super::fixtures::{self, ADMINISTRATOR, MEMBER};
+use super::{json, run, Harness};
+
+/// The permission bitfield of a member who may manage the server, but is not
+/// an administrator.
+const MANAGE_GUILD: &str = "32";
+
+const WEBHOOK_PATH: &str = "/webhooks/4343/audit-token";
+const WEBHOOK_MESSAGE: &str =
+    r#"{"id": "1", "channel_id": "2", "content": "", "timestamp": "2022-09-01T00:00:00+00:00"}"#;
+
+#[test]
+fn config_changes_are_mirrored_to_the_audit_webhook() {
+    run(async {
+        let mut harness = Harness::new();
+        harness.set_var(
+            "AUDIT_WEBHOOK_URL",
+            &format!("https://discord.com/api{}", WEBHOOK_PATH),
+        );
+        harness
+            .discord
+            .respond("POST", WEBHOOK_PATH, 200, WEBHOOK_MESSAGE);
+
+        harness
+            .send(&fixtures::guild_command(
+                "config",
+                &[("setting", "language"), ("value", "ja")],
+                ADMINISTRATOR,
+            ))
+            .await;
+
+        let requests = harness.discord.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].path, format!("{}?wait=true", WEBHOOK_PATH));
+        let embed = &serde_json::from_str::<Value>(&requests[0].body).unwrap()["embeds"][0];
+        assert_eq!(embed["title"], "Configuration changed");
+        assert_eq!(
+            embed["description"],
+            format!("In guild {}", fixtures::GUILD_ID)
+        );
+    })
+}
+
+async fn configure(harness: &Harness, setting: &str, value: &str) {
+    harness
+        .send(&fixtures::guild_command(
+            "config",
+            &[("setting", setting), ("value", value)],
+            ADMINISTRATOR,
+        ))
+        .await;
+}
+
+#[test]
+fn audit_lists_the_latest_changes_first() {
+    run(async {
+        let harness = Harness::new();
+        let empty = harness
+            .send(&fixtures::guild_command("audit", &[], ADMINISTRATOR))
+            .await;
+        configure(&harness, "precision", "4").await;
+        configure(&harness, "language", "ja").await;
+        // Changes to a user's own defaults stay out of the guild's log
+        harness
+            .fixer
+            .respond("GET", "/latest", 200, fixtures::FIXER_LATEST_AUD_JPY);
+        harness
+            .send(&fixtures::guild_command(
+                "xe",
+                &[("from", "AUD"), ("to", "JPY"), ("set_defaults", "True")],
+                ADMINISTRATOR,
+            ))
+            .await;
+
+        let response = harness
+            .send(&fixtures::guild_command("audit", &[], ADMINISTRATOR))
+            .await;
+
+        assert_eq!(
+            json(&empty)["data"]["embeds"][0]["description"],
+            Language::En.text(Text::NoAuditEntries)
+        );
+        let embed = &json(&response)["data"]["embeds"][0];
+        assert_eq!(embed["description"], Language::Ja.text(Text::AuditHelp));
+        let fields = embed["fields"].as_array().unwrap();
+        assert_eq!(fields.len(), 2);
+        assert_eq!(fields[0]["name"], "/config language");
+        assert!(fields[0]["value"]
+            .as_str()
+            .unwrap()
+            .ends_with("`null` → `\"ja\"`"));
+        assert_eq!(fields[1]["name"], "/config precision");
+        assert!(fields[1]["value"]
+            .as_str()
+            .unwrap()
+            .contains(&format!("by {}", fixtures::USER_ID)));
+    })
+}
+
+#[test]
+fn audit_lists_at_most_ten_changes() {
+    run(async {
+        let harness = Harness::new();
+        for precision in 0..12 {
+            configure(&harness, "precision", &precision.to_string()).await;
+        }
+
+        let response = harness
+            .send(&fixtures::guild_command("audit", &[], ADMINISTRATOR))
+            .await;
+
+        let fields = json(&response)["data"]["embeds"][0]["fields"].clone();
+        assert_eq!(fields.as_array().unwrap().len(), 10);
+        assert!(fields[0]["value"]
+            .as_str()
+            .unwrap()
+            .ends_with("`10` → `11`"));
+    })
+}
+
+#[test]
+fn audit_needs_an_administrator_in_a_guild() {
+    run(async {
+        let harness = Harness::new();
+        configure(&harness, "precision", "4").await;
+
+        let member = harness
+            .send(&fixtures::guild_command("audit", &[], MEMBER))
+            .await;
+        let manager = harness
+            .send(&fixtures::guild_command("audit", &[], MANAGE_GUILD))
+            .await;
+        let dm = harness.send(&fixtures::dm_command("audit", &[])).await;
+
+        for response in [&member, &manager].iter() {
+            let data = &json(response)["data"];
+            assert_eq!(
+                data["content"],
+                Language::En.text(Text::AdministratorRequired)
+            );
+            assert_eq!(data["embeds"], Value::Null);
+        }
+        assert_eq!(
+            json(&dm)["data"]["content"],
+            Language::En.text(Text::GuildOnly)
+        );
+    })
+}
+
+#[test]
+fn changes_made_at_once_are_all_kept_newest_first() {
+    run(async {
+        let harness = Harness::new();
+        let (ctx, _) = harness.context();
+
+        for precision in 0..5 {
+            audit::record(
+                &ctx,
+                Some(fixtures::GUILD_ID),
+                "config",
+                "precision",
+                Value::Null,
+                Value::from(precision),
+            )
+            .await
+            .unwrap();
+        }
+
+        let entries = audit::recent(harness.store(), &guild_log_prefix(fixtures::GUILD_ID), 10)
+            .await
+            .unwrap();
+        assert_eq!(entries.len(), 5);
+        assert_eq!(entries[0].new_value, 4);
+    })
+}
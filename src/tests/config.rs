@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+
+use ed25519_dalek::Keypair;
+use rand::rngs::OsRng;
+
+use crate::config::{Config, UpstreamMode};
+use crate::logger::{Format, Level};
+use crate::upstream::Upstream;
+
+fn vars(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+    pairs
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+fn discord_vars() -> HashMap<String, String> {
+    let public_key = hex::encode(Keypair::generate(&mut OsRng).public.to_bytes());
+    vars(&[
+        ("DISCORD_PUBLIC_KEY", &public_key),
+        ("DISCORD_APPLICATION_ID", "1234567890"),
+        ("DISCORD_TOKEN", "bot-token"),
+    ])
+}
+
+#[test]
+fn every_missing_key_is_named_at_once() {
+    let none = |_: &str| None;
+
+    let err = Config::load(none, none).unwrap_err();
+
+    assert_eq!(
+        err.missing,
+        vec![
+            "DISCORD_PUBLIC_KEY",
+            "DISCORD_APPLICATION_ID",
+            "DISCORD_TOKEN"
+        ]
+    );
+    assert_eq!(
+        err.to_string(),
+        "Configuration error: missing DISCORD_PUBLIC_KEY, DISCORD_APPLICATION_ID, DISCORD_TOKEN"
+    );
+}
+
+#[test]
+fn invalid_values_are_reported_rather_than_ignored() {
+    let mut vars = discord_vars();
+    vars.extend(self::vars(&[
+        ("DISCORD_APPLICATION_ID", "nuko"),
+        ("RATE_CACHE_TTL", "an hour"),
+        ("ERROR_WEBHOOK_URL", "https://example.com/hook"),
+        ("UPSTREAM_MODE", "mock"),
+        ("FIXER_API_BASE", "ftp://fixer"),
+        ("JOB_SCHEDULE", "nap=0 * * * *"),
+    ]));
+    let var = |key: &str| vars.get(key).cloned();
+
+    let err = Config::load(var, var).unwrap_err();
+
+    let keys = err
+        .invalid
+        .iter()
+        .map(|(key, _)| *key)
+        .collect::<Vec<&str>>();
+    assert!(err.missing.is_empty());
+    assert_eq!(
+        keys,
+        vec![
+            "DISCORD_APPLICATION_ID",
+            "ERROR_WEBHOOK_URL",
+            "RATE_CACHE_TTL",
+            "FIXER_API_BASE",
+            "JOB_SCHEDULE",
+            "UPSTREAM_MODE"
+        ]
+    );
+    assert!(err
+        .to_string()
+        .contains("UPSTREAM_MODE is invalid (unknown upstream mode 'mock')"));
+}
+
+#[test]
+fn defaults_apply_to_unset_and_empty_keys() {
+    let mut vars = discord_vars();
+    vars.extend(self::vars(&[("LOG_LEVEL", " "), ("RATE_CACHE_TTL", "5")]));
+    let var = |key: &str| vars.get(key).cloned();
+
+    let config = Config::load(var, var).unwrap();
+
+    assert_eq!(config.log_level, Level::Info);
+    assert_eq!(config.log_format, Format::Json);
+    assert_eq!(config.signature_max_age, 300);
+    assert!(!config.deduplicate_interactions);
+    assert_eq!(config.upstream_mode, UpstreamMode::Live);
+    assert_eq!(config.fixer_token, None);
+    // Clamped to the shortest TTL KV accepts
+    assert_eq!(config.cache_ttls.rate, 60);
+    assert_eq!(config.base_url(Upstream::Jisho), "https://jisho.org/api/v1");
+    assert!(config.is_set("DISCORD_TOKEN"));
+    assert!(!config.is_set("LOG_LEVEL"));
+}
+
+#[test]
+fn secrets_are_only_read_as_secrets() {
+    let vars = discord_vars();
+    let secrets = self::vars(&[("DISCORD_TOKEN", "secret-token"), ("ADMIN_TOKEN", "admin")]);
+
+    let config = Config::load(
+        |key| vars.get(key).cloned(),
+        |key| secrets.get(key).cloned(),
+    )
+    .unwrap();
+
+    assert_eq!(config.discord_token, "secret-token");
+    assert_eq!(config.admin_token.as_deref(), Some("admin"));
+    assert_eq!(config.secrets(), vec!["secret-token", "admin"]);
+}
+
+#[test]
+fn commands_run_without_the_discord_keys() {
+    let vars = vars(&[("CURR_CONV_TOKEN", "fixer-token")]);
+
+    let config = Config::without_discord(|key| vars.get(key).cloned()).unwrap();
+
+    assert_eq!(config.discord_token, "");
+    assert_eq!(config.fixer_token.as_deref(), Some("fixer-token"));
+}
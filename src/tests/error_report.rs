@@ -0,0 +1,157 @@
+use serde_json::Value;
+
+use crate::error_report::ReportThrottle;
+use crate::store::migration::{read_record, write_record};
+
+use super::fixtures::{self, MEMBER};
+use super::{run, Harness};
+
+const WEBHOOK_PATH: &str = "/webhooks/4242/hook-token";
+const WEBHOOK_MESSAGE: &str =
+    r#"{"id": "1", "channel_id": "2", "content": "", "timestamp": "2022-09-01T00:00:00+00:00"}"#;
+
+/// A harness reporting errors to a webhook on the Discord stand-in.
+fn reporting() -> Harness {
+    let mut harness = Harness::new();
+    harness.set_var(
+        "ERROR_WEBHOOK_URL",
+        &format!("https://discord.com/api{}", WEBHOOK_PATH),
+    );
+    harness
+        .discord
+        .respond("POST", WEBHOOK_PATH, 200, WEBHOOK_MESSAGE);
+    harness
+}
+
+/// The embeds posted to the webhook, oldest first.
+fn reports(harness: &Harness) -> Vec<Value> {
+    harness
+        .discord
+        .requests()
+        .iter()
+        .filter(|request| request.path.starts_with(WEBHOOK_PATH))
+        .map(|request| serde_json::from_str::<Value>(&request.body).unwrap()["embeds"][0].clone())
+        .collect()
+}
+
+fn field<'a>(report: &'a Value, name: &str) -> &'a Value {
+    &report["fields"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|field| field["name"] == name)
+        .unwrap_or_else(|| panic!("no {} field in {}", name, report))["value"]
+}
+
+#[test]
+fn failed_interactions_are_reported_once_per_kind() {
+    run(async {
+        let harness = reporting();
+
+        let response = harness
+            .send(&fixtures::guild_command(
+                "nope",
+                &[("name", "Loki")],
+                MEMBER,
+            ))
+            .await;
+        harness
+            .send(&fixtures::guild_command("nope", &[], MEMBER))
+            .await;
+
+        assert_eq!(response.status, 500);
+        let reports = reports(&harness);
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0]["title"], "Interaction failed: unknown_command");
+        assert_eq!(field(&reports[0], "Command"), "nope");
+        assert_eq!(field(&reports[0], "Options"), "name=Loki");
+        assert_eq!(field(&reports[0], "Guild"), fixtures::GUILD_ID);
+        assert!(field(&reports[0], "Context")
+            .as_str()
+            .unwrap()
+            .contains("UnknownCommand(\"nope\")"));
+        let throttle =
+            read_record::<ReportThrottle>(harness.store(), "error_report:unknown_command")
+                .await
+                .unwrap()
+                .unwrap();
+        assert_eq!(throttle.held_back, 1);
+    })
+}
+
+#[test]
+fn upstream_failures_are_reported_by_upstream_and_status() {
+    run(async {
+        let harness = reporting();
+        harness.fixer.respond(
+            "GET",
+            "/latest",
+            429,
+            r#"{"message": "You have exceeded your daily/monthly API rate limit."}"#,
+        );
+
+        harness
+            .send(&fixtures::dm_command(
+                "xe",
+                &[("from", "AUD"), ("to", "JPY"), ("amount", "50")],
+            ))
+            .await;
+
+        let reports = reports(&harness);
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0]["title"], "Interaction failed: fixer_429");
+        assert_eq!(field(&reports[0], "Guild"), "Direct message");
+        assert_eq!(field(&reports[0], "Options"), "from=AUD to=JPY amount=50");
+        let context = field(&reports[0], "Context").as_str().unwrap();
+        assert!(context.contains("API rate limit"));
+        assert!(!reports[0].to_string().contains("fixer-token"));
+    })
+}
+
+#[test]
+fn reports_count_the_failures_held_back() {
+    run(async {
+        let harness = reporting();
+        let throttle = ReportThrottle {
+            reported_at: chrono::Utc::now().timestamp() - 60 * 60,
+            held_back: 4,
+        };
+        write_record(
+            harness.store(),
+            "error_report:unknown_command",
+            &throttle,
+            None,
+        )
+        .await
+        .unwrap();
+
+        harness
+            .send(&fixtures::guild_command("nope", &[], MEMBER))
+            .await;
+
+        let reports = reports(&harness);
+        assert_eq!(
+            field(&reports[0], "Held back"),
+            "4 more since the last report"
+        );
+    })
+}
+
+#[test]
+fn nothing_is_reported_without_a_webhook() {
+    run(async {
+        let harness = Harness::new();
+
+        harness
+            .send(&fixtures::guild_command("nope", &[], MEMBER))
+            .await;
+
+        assert!(harness.discord.requests().is_empty());
+        assert!(harness
+            .store()
+            .get("error_report:unknown_command")
+            .await
+            .unwrap()
+            .is_none());
+    })
+}
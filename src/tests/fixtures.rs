@@ -0,0 +1,117 @@
+//! Interactions shaped the way Discord sends them, and upstream responses for
+//! the stand-in servers.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use serde_json::{json, Value};
+
+pub(crate) const APPLICATION_ID: &str = "1000000000000000000";
+pub(crate) const GUILD_ID: &str = "2000000000000000000";
+pub(crate) const USER_ID: &str = "3000000000000000000";
+/// The permission bitfield of a member who may only use commands.
+pub(crate) const MEMBER: &str = "2147483648";
+/// The permission bitfield of a server administrator.
+pub(crate) const ADMINISTRATOR: &str = "8";
+
+pub(crate) const JISHO_NEKO: &str = include_str!("fixtures/jisho_neko.json");
+pub(crate) const JISHO_EMPTY: &str = r#"{"meta": {"status": 200}, "data": []}"#;
+pub(crate) const FIXER_LATEST_AUD_JPY: &str = include_str!("fixtures/fixer_latest_aud_jpy.json");
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(4_000_000_000_000_000_000);
+
+/// Every interaction gets its own ID, as deduplication would reject repeats.
+fn next_id() -> String {
+    NEXT_ID.fetch_add(1, Ordering::Relaxed).to_string()
+}
+
+fn user() -> Value {
+    json!({
+        "id": USER_ID,
+        "username": "loki",
+        "avatar": "8342729096ea3675442027381ff50dfe",
+        "avatar_decoration": null,
+        "discriminator": "0",
+        "public_flags": 0,
+    })
+}
+
+fn member(permissions: &str) -> Value {
+    json!({
+        "user": user(),
+        "roles": [],
+        "premium_since": null,
+        "permissions": permissions,
+        "pending": false,
+        "nick": null,
+        "mute": false,
+        "joined_at": "2022-08-01T12:00:00.000000+00:00",
+        "is_pending": false,
+        "deaf": false,
+    })
+}
+
+/// String options as `(name, value)` pairs, the last one focused if `focused`.
+fn options(options: &[(&str, &str)], focused: bool) -> Value {
+    let last = options.len().saturating_sub(1);
+    options
+        .iter()
+        .enumerate()
+        .map(|(index, (name, value))| {
+            let mut option = json!({ "name": name, "type": 3, "value": value });
+            if focused && index == last {
+                option["focused"] = json!(true);
+            }
+            option
+        })
+        .collect()
+}
+
+fn interaction(ty: u8, data: Option<Value>) -> Value {
+    json!({
+        "id": next_id(),
+        "application_id": APPLICATION_ID,
+        "type": ty,
+        "data": data,
+        "token": format!("interaction-token-{}", next_id()),
+        "version": 1,
+    })
+}
+
+pub(crate) fn ping() -> Value {
+    interaction(1, None)
+}
+
+/// A command used in a guild by a member with `permissions`.
+pub(crate) fn guild_command(name: &str, args: &[(&str, &str)], permissions: &str) -> Value {
+    let mut interaction = interaction(
+        2,
+        Some(json!({ "id": next_id(), "name": name, "type": 1, "options": options(args, false) })),
+    );
+    interaction["guild_id"] = json!(GUILD_ID);
+    interaction["channel_id"] = json!(next_id());
+    interaction["member"] = member(permissions);
+    interaction
+}
+
+/// A command used in a direct message, which comes with a user but no member.
+pub(crate) fn dm_command(name: &str, args: &[(&str, &str)]) -> Value {
+    let mut interaction = interaction(
+        2,
+        Some(json!({ "id": next_id(), "name": name, "type": 1, "options": options(args, false) })),
+    );
+    interaction["channel_id"] = json!(next_id());
+    interaction["user"] = user();
+    interaction
+}
+
+/// An autocomplete request in a guild, with the last option focused.
+pub(crate) fn autocomplete(name: &str, args: &[(&str, &str)]) -> Value {
+    let mut interaction = interaction(
+        4,
+        Some(json!({ "id": next_id(), "name": name, "type": 1, "options": options(args, true) })),
+    );
+    interaction["guild_id"] = json!(GUILD_ID);
+    interaction["channel_id"] = json!(next_id());
+    interaction["member"] = member(MEMBER);
+    interaction
+}
@@ -0,0 +1,176 @@
+use crate::audit::{self, guild_log_prefix};
+use crate::guild_config::GuildConfig;
+use crate::locale::{Language, Text};
+
+use super::fixtures::{self, ADMINISTRATOR, MEMBER};
+use super::{json, run, Harness};
+
+async fn stored(harness: &Harness) -> GuildConfig {
+    GuildConfig::load(harness.store(), fixtures::GUILD_ID)
+        .await
+        .unwrap()
+}
+
+#[test]
+fn config_needs_manage_server_in_a_guild() {
+    run(async {
+        let harness = Harness::new();
+
+        let member = harness
+            .send(&fixtures::guild_command(
+                "config",
+                &[("setting", "precision"), ("value", "4")],
+                MEMBER,
+            ))
+            .await;
+        let dm = harness
+            .send(&fixtures::dm_command(
+                "config",
+                &[("setting", "precision"), ("value", "4")],
+            ))
+            .await;
+
+        assert_eq!(
+            json(&member)["data"]["content"],
+            Language::En.text(Text::ManageGuildRequired)
+        );
+        assert_eq!(
+            json(&dm)["data"]["content"],
+            Language::En.text(Text::GuildOnly)
+        );
+        assert_eq!(stored(&harness).await, GuildConfig::default());
+    })
+}
+
+#[test]
+fn config_stores_each_setting() {
+    run(async {
+        let harness = Harness::new();
+        let settings = [
+            ("currency_from", "AUD"),
+            ("currency_to", "JPY"),
+            ("precision", "4"),
+            ("embed_color", "#123456"),
+            ("language", "ja"),
+            ("disable_command", "jisho"),
+            ("disable_command", "hey"),
+            ("enable_command", "jisho"),
+        ];
+
+        for (setting, value) in settings.iter() {
+            let response = harness
+                .send(&fixtures::guild_command(
+                    "config",
+                    &[("setting", setting), ("value", value)],
+                    ADMINISTRATOR,
+                ))
+                .await;
+            assert_eq!(response.status, 200);
+        }
+
+        assert_eq!(
+            stored(&harness).await,
+            GuildConfig {
+                currency_from: Some("AUD".into()),
+                currency_to: Some("JPY".into()),
+                precision: Some(4),
+                embed_color: Some(0x123456),
+                language: Some(Language::Ja),
+                disabled_commands: vec!["hey".into()],
+            }
+        );
+    })
+}
+
+#[test]
+fn config_clears_settings_given_no_value() {
+    run(async {
+        let harness = Harness::new();
+        GuildConfig {
+            currency_from: Some("AUD".into()),
+            precision: Some(4),
+            ..GuildConfig::default()
+        }
+        .save(harness.store(), fixtures::GUILD_ID)
+        .await
+        .unwrap();
+
+        harness
+            .send(&fixtures::guild_command(
+                "config",
+                &[("setting", "precision")],
+                ADMINISTRATOR,
+            ))
+            .await;
+
+        assert_eq!(
+            stored(&harness).await,
+            GuildConfig {
+                currency_from: Some("AUD".into()),
+                ..GuildConfig::default()
+            }
+        );
+    })
+}
+
+#[test]
+fn config_rejects_invalid_values() {
+    run(async {
+        let harness = Harness::new();
+        let invalid = [
+            ("currency_from", "ZZZ"),
+            ("precision", "13"),
+            ("embed_color", "#1234567"),
+            ("language", "xx"),
+            ("disable_command", "config"),
+            ("disable_command", "mydata"),
+            ("disable_command", "nope"),
+            ("enable_command", "nope"),
+            ("colour", "red"),
+        ];
+
+        for (setting, value) in invalid.iter() {
+            let response = harness
+                .send(&fixtures::guild_command(
+                    "config",
+                    &[("setting", setting), ("value", value)],
+                    ADMINISTRATOR,
+                ))
+                .await;
+            assert_eq!(
+                json(&response)["data"]["content"],
+                format!("Invalid value : {} = {}", setting, value)
+            );
+        }
+
+        assert_eq!(stored(&harness).await, GuildConfig::default());
+    })
+}
+
+#[test]
+fn config_leaves_unchanged_settings_out_of_the_audit_log() {
+    run(async {
+        let harness = Harness::new();
+        let settings = [
+            ("precision", "4"),
+            ("precision", "4"),
+            ("enable_command", "hey"),
+        ];
+
+        for (setting, value) in settings.iter() {
+            harness
+                .send(&fixtures::guild_command(
+                    "config",
+                    &[("setting", setting), ("value", value)],
+                    ADMINISTRATOR,
+                ))
+                .await;
+        }
+
+        let entries = audit::recent(harness.store(), &guild_log_prefix(fixtures::GUILD_ID), 10)
+            .await
+            .unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].setting, "precision");
+    })
+}
@@ -0,0 +1,698 @@
+use serde_json::{json, Value};
+
+use crate::command::{GuildCommandSync, GUILD_BATCH_SIZE};
+use crate::guild_config::GuildConfig;
+use crate::preferences::UserPreferences;
+
+use super::fixtures::{self, ADMINISTRATOR, MEMBER};
+use super::{json, run, Harness};
+
+#[test]
+fn ping_is_answered_with_pong() {
+    run(async {
+        let harness = Harness::new();
+
+        let response = harness.send(&fixtures::ping()).await;
+
+        assert_eq!(response.status, 200);
+        assert_eq!(json(&response), json!({ "type": 1, "data": null }));
+    })
+}
+
+#[test]
+fn requests_signed_with_another_key_are_rejected() {
+    run(async {
+        let harness = Harness::new();
+        let other = Harness::new();
+        let body = fixtures::ping().to_string();
+        let timestamp = chrono::Utc::now().timestamp().to_string();
+
+        let response = harness
+            .send_raw(
+                &[
+                    ("content-type", "application/json"),
+                    ("x-signature-ed25519", &other.sign(&timestamp, &body)),
+                    ("x-signature-timestamp", &timestamp),
+                ],
+                body,
+            )
+            .await;
+
+        assert_eq!(response.status, 401);
+    })
+}
+
+#[test]
+fn tampered_bodies_are_rejected() {
+    run(async {
+        let harness = Harness::new();
+        let timestamp = chrono::Utc::now().timestamp().to_string();
+        let signature = harness.sign(&timestamp, &fixtures::ping().to_string());
+
+        let response = harness
+            .send_raw(
+                &[
+                    ("content-type", "application/json"),
+                    ("x-signature-ed25519", &signature),
+                    ("x-signature-timestamp", &timestamp),
+                ],
+                fixtures::guild_command("hey", &[("name", "Loki")], MEMBER).to_string(),
+            )
+            .await;
+
+        assert_eq!(response.status, 401);
+    })
+}
+
+#[test]
+fn old_timestamps_are_rejected() {
+    run(async {
+        let harness = Harness::new();
+        let body = fixtures::ping().to_string();
+        let timestamp = (chrono::Utc::now().timestamp() - 60 * 60).to_string();
+
+        let response = harness
+            .send_raw(
+                &[
+                    ("content-type", "application/json"),
+                    ("x-signature-ed25519", &harness.sign(&timestamp, &body)),
+                    ("x-signature-timestamp", &timestamp),
+                ],
+                body,
+            )
+            .await;
+
+        assert_eq!(response.status, 401);
+    })
+}
+
+#[test]
+fn unsigned_requests_are_rejected() {
+    run(async {
+        let harness = Harness::new();
+
+        let response = harness
+            .send_raw(
+                &[("content-type", "application/json")],
+                fixtures::ping().to_string(),
+            )
+            .await;
+
+        assert_eq!(response.status, 400);
+    })
+}
+
+#[test]
+fn repeated_interactions_are_rejected_when_deduplicating() {
+    run(async {
+        let mut harness = Harness::new();
+        harness.set_var("DEDUPLICATE_INTERACTIONS", "true");
+        let interaction = fixtures::guild_command("hey", &[("name", "Loki")], MEMBER);
+
+        assert_eq!(harness.send(&interaction).await.status, 200);
+        assert_eq!(harness.send(&interaction).await.status, 401);
+    })
+}
+
+#[test]
+fn hey_greets_by_name() {
+    run(async {
+        let harness = Harness::new();
+
+        let response = harness
+            .send(&fixtures::guild_command("hey", &[("name", "Loki")], MEMBER))
+            .await;
+
+        assert_eq!(
+            json(&response),
+            json!({
+                "type": 4,
+                "data": { "content": "Hey, Loki!", "choices": null, "embeds": null },
+            })
+        );
+    })
+}
+
+#[test]
+fn hey_suggests_names() {
+    run(async {
+        let harness = Harness::new();
+
+        let response = harness
+            .send(&fixtures::autocomplete("hey", &[("name", "lo")]))
+            .await;
+
+        assert_eq!(
+            json(&response),
+            json!({
+                "type": 8,
+                "data": {
+                    "content": null,
+                    "embeds": null,
+                    "choices": [
+                        { "name": "loki", "value": "Loki" },
+                        { "name": "icecream", "value": "IceCream" },
+                        { "name": "yoroshi", "value": "Yoroshi" },
+                    ],
+                },
+            })
+        );
+    })
+}
+
+#[test]
+fn unknown_commands_fail() {
+    run(async {
+        let harness = Harness::new();
+
+        let response = harness
+            .send(&fixtures::guild_command("nope", &[], MEMBER))
+            .await;
+
+        assert_eq!(response.status, 500);
+    })
+}
+
+fn jisho_neko() -> Value {
+    json!({
+        "type": 4,
+        "data": {
+            "content": null,
+            "choices": null,
+            "embeds": [{
+                "title": "",
+                "description": "",
+                "url": null,
+                "thumbnail": null,
+                "footer": null,
+                "fields": [
+                    { "name": "Word searched", "value": "neko", "inline": false },
+                    { "name": "English", "value": "cat", "inline": true },
+                    { "name": "Japanese", "value": "猫", "inline": true },
+                    { "name": "Reading", "value": "ねこ", "inline": true },
+                    {
+                        "name": "Extras",
+                        "value": "[📗](https://jisho.org/word/猫) | [🔍](https://jisho.org/search/neko)",
+                        "inline": false,
+                    },
+                ],
+                "color": 0x00ff00,
+            }],
+        },
+    })
+}
+
+#[test]
+fn jisho_looks_words_up_once() {
+    run(async {
+        let harness = Harness::new();
+        harness
+            .jisho
+            .respond("GET", "/search/words", 200, fixtures::JISHO_NEKO);
+
+        let first = harness
+            .send(&fixtures::guild_command(
+                "jisho",
+                &[("word", "neko")],
+                MEMBER,
+            ))
+            .await;
+        let second = harness
+            .send(&fixtures::dm_command("jisho", &[("word", "neko")]))
+            .await;
+
+        assert_eq!(json(&first), jisho_neko());
+        assert_eq!(json(&second), jisho_neko());
+        let requests = harness.jisho.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].path, "/search/words?keyword=neko");
+    })
+}
+
+#[test]
+fn jisho_reports_unknown_words() {
+    run(async {
+        let harness = Harness::new();
+        harness
+            .jisho
+            .respond("GET", "/search/words", 200, fixtures::JISHO_EMPTY);
+
+        let response = harness
+            .send(&fixtures::guild_command(
+                "jisho",
+                &[("word", "zzz")],
+                MEMBER,
+            ))
+            .await;
+
+        assert_eq!(
+            json(&response)["data"]["embeds"][0]["description"],
+            "No data found for that word"
+        );
+    })
+}
+
+fn xe_aud_jpy() -> Value {
+    json!({
+        "type": 4,
+        "data": {
+            "content": null,
+            "choices": null,
+            "embeds": [{
+                "title": "Exchange Rate",
+                "description": "50 AUD --> 4712.6500 JPY",
+                "url": null,
+                "thumbnail": null,
+                "footer": null,
+                "fields": [],
+                "color": 0xfdc835,
+            }],
+        },
+    })
+}
+
+#[test]
+fn xe_converts_with_the_latest_rate() {
+    run(async {
+        let harness = Harness::new();
+        harness
+            .fixer
+            .respond("GET", "/latest", 200, fixtures::FIXER_LATEST_AUD_JPY);
+
+        let response = harness
+            .send(&fixtures::guild_command(
+                "xe",
+                &[("from", "AUD"), ("to", "JPY"), ("amount", "50")],
+                MEMBER,
+            ))
+            .await;
+
+        assert_eq!(json(&response), xe_aud_jpy());
+        let requests = harness.fixer.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].path, "/latest?symbols=JPY&base=AUD");
+        assert_eq!(requests[0].headers["apikey"], "fixer-token");
+    })
+}
+
+#[test]
+fn xe_works_in_direct_messages() {
+    run(async {
+        let harness = Harness::new();
+        harness
+            .fixer
+            .respond("GET", "/latest", 200, fixtures::FIXER_LATEST_AUD_JPY);
+
+        let response = harness
+            .send(&fixtures::dm_command(
+                "xe",
+                &[("from", "AUD"), ("to", "JPY"), ("amount", "50")],
+            ))
+            .await;
+
+        assert_eq!(json(&response), xe_aud_jpy());
+    })
+}
+
+#[test]
+fn disabled_commands_answer_with_a_notice() {
+    run(async {
+        let harness = Harness::new();
+
+        let denied = harness
+            .send(&fixtures::guild_command(
+                "config",
+                &[("setting", "disable_command"), ("value", "hey")],
+                MEMBER,
+            ))
+            .await;
+        assert_eq!(
+            json(&denied)["data"]["content"],
+            "You need the Manage Server permission to use this command"
+        );
+
+        harness
+            .send(&fixtures::guild_command(
+                "config",
+                &[("setting", "disable_command"), ("value", "hey")],
+                ADMINISTRATOR,
+            ))
+            .await;
+        let response = harness
+            .send(&fixtures::guild_command("hey", &[("name", "Loki")], MEMBER))
+            .await;
+
+        assert_eq!(
+            json(&response),
+            json!({
+                "type": 4,
+                "data": {
+                    "content": "This command is disabled in this server",
+                    "choices": null,
+                    "embeds": null,
+                    "flags": 64,
+                },
+            })
+        );
+        let audit = harness
+            .store()
+            .list(&format!("audit:guild:{}:", fixtures::GUILD_ID), None)
+            .await
+            .unwrap();
+        assert_eq!(audit.keys.len(), 1);
+    })
+}
+
+#[test]
+fn register_overwrites_the_global_commands() {
+    run(async {
+        let harness = Harness::new();
+        let path = format!("/applications/{}/commands", fixtures::APPLICATION_ID);
+        harness.discord.respond("PUT", &path, 200, "[]");
+
+        let response = harness.register().await;
+
+        assert_eq!(response.status, 200);
+        let requests = harness.discord.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].path, path);
+        assert_eq!(requests[0].headers["authorization"], "Bot bot-token");
+        let names = serde_json::from_str::<Vec<Value>>(&requests[0].body)
+            .unwrap()
+            .iter()
+            .map(|command| command["name"].as_str().unwrap().to_string())
+            .collect::<Vec<String>>();
+        assert_eq!(
+            names,
+            ["audit", "config", "hey", "jisho", "mydata", "stats", "xe"]
+        );
+    })
+}
+
+#[test]
+fn register_reports_the_last_rate_limit_when_giving_up() {
+    run(async {
+        let harness = Harness::new();
+        let path = format!("/applications/{}/commands", fixtures::APPLICATION_ID);
+        for retry_after in ["0.01", "0.02", "0.75"].iter() {
+            harness.discord.respond_once(
+                "PUT",
+                &path,
+                429,
+                format!(
+                    r#"{{"message": "Slow down", "retry_after": {}}}"#,
+                    retry_after
+                ),
+            );
+        }
+
+        let response = harness.register().await;
+
+        assert_eq!(response.status, 502);
+        assert_eq!(harness.discord.requests().len(), 3);
+        assert!(String::from_utf8_lossy(&response.body).contains("retry after 750ms"));
+    })
+}
+
+fn command_names(body: &str) -> Vec<String> {
+    serde_json::from_str::<Vec<Value>>(body)
+        .unwrap()
+        .iter()
+        .map(|command| command["name"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[test]
+fn register_per_guild_leaves_out_disabled_commands() {
+    run(async {
+        let mut harness = Harness::new();
+        harness.set_var("GUILD_COMMANDS", "true");
+        GuildConfig {
+            disabled_commands: vec!["xe".into()],
+            ..GuildConfig::default()
+        }
+        .save(harness.store(), fixtures::GUILD_ID)
+        .await
+        .unwrap();
+        let global = format!("/applications/{}/commands", fixtures::APPLICATION_ID);
+        let guild = format!(
+            "/applications/{}/guilds/{}/commands",
+            fixtures::APPLICATION_ID,
+            fixtures::GUILD_ID
+        );
+        harness.discord.respond("PUT", &global, 200, "[]");
+        harness.discord.respond(
+            "GET",
+            "/users/@me/guilds",
+            200,
+            json!([{ "id": fixtures::GUILD_ID, "name": "Nuko" }]).to_string(),
+        );
+        harness.discord.respond("PUT", &guild, 200, "[]");
+
+        let response = harness.register().await;
+
+        assert_eq!(response.status, 200);
+        let requests = harness.discord.requests();
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[0].path, global);
+        assert_eq!(command_names(&requests[0].body), ["config", "mydata"]);
+        assert_eq!(requests[1].path, "/users/@me/guilds?limit=25");
+        assert_eq!(requests[2].path, guild);
+        assert_eq!(
+            command_names(&requests[2].body),
+            ["audit", "hey", "jisho", "stats"]
+        );
+    })
+}
+
+#[test]
+fn register_per_guild_carries_on_with_the_next_batch() {
+    run(async {
+        let mut harness = Harness::new();
+        harness.set_var("GUILD_COMMANDS", "true");
+        let global = format!("/applications/{}/commands", fixtures::APPLICATION_ID);
+        harness.discord.respond("PUT", &global, 200, "[]");
+        let ids = (1..=GUILD_BATCH_SIZE + 1)
+            .map(|id| id.to_string())
+            .collect::<Vec<String>>();
+        for id in &ids {
+            let guild = format!(
+                "/applications/{}/guilds/{}/commands",
+                fixtures::APPLICATION_ID,
+                id
+            );
+            harness.discord.respond("PUT", &guild, 200, "[]");
+        }
+        let page = |ids: &[String]| {
+            json!(ids
+                .iter()
+                .map(|id| json!({ "id": id, "name": "Nuko" }))
+                .collect::<Vec<Value>>())
+            .to_string()
+        };
+        harness.discord.respond_once(
+            "GET",
+            "/users/@me/guilds",
+            200,
+            page(&ids[..GUILD_BATCH_SIZE]),
+        );
+        harness.discord.respond_once(
+            "GET",
+            "/users/@me/guilds",
+            200,
+            page(&ids[GUILD_BATCH_SIZE..]),
+        );
+
+        harness.register().await;
+        let first = harness.discord.requests().len();
+        harness.register().await;
+
+        let requests = harness.discord.requests();
+        assert_eq!(first, 2 + GUILD_BATCH_SIZE);
+        assert_eq!(requests.len(), first + 3);
+        assert_eq!(
+            requests[first + 1].path,
+            format!("/users/@me/guilds?limit=25&after={}", GUILD_BATCH_SIZE)
+        );
+        let sync = GuildCommandSync::load(harness.store()).await.unwrap();
+        assert!(sync.enabled);
+        assert!(!sync.pending);
+    })
+}
+
+#[test]
+fn turning_guild_commands_off_clears_them_once() {
+    run(async {
+        let mut harness = Harness::new();
+        harness.set_var("GUILD_COMMANDS", "true");
+        let global = format!("/applications/{}/commands", fixtures::APPLICATION_ID);
+        let guild = format!(
+            "/applications/{}/guilds/{}/commands",
+            fixtures::APPLICATION_ID,
+            fixtures::GUILD_ID
+        );
+        harness.discord.respond("PUT", &global, 200, "[]");
+        harness.discord.respond(
+            "GET",
+            "/users/@me/guilds",
+            200,
+            json!([{ "id": fixtures::GUILD_ID, "name": "Nuko" }]).to_string(),
+        );
+        harness.discord.respond("PUT", &guild, 200, "[]");
+        harness.register().await;
+
+        harness.set_var("GUILD_COMMANDS", "false");
+        let cleared = harness.register().await;
+        let again = harness.register().await;
+
+        assert_eq!(cleared.status, 200);
+        assert_eq!(again.status, 200);
+        let requests = harness.discord.requests();
+        assert_eq!(requests.len(), 7);
+        assert_eq!(requests[5].path, guild);
+        assert_eq!(requests[5].body, "[]");
+        assert_eq!(requests[6].path, global);
+    })
+}
+
+#[test]
+fn disabling_a_command_updates_the_guild_commands() {
+    run(async {
+        let mut harness = Harness::new();
+        harness.set_var("GUILD_COMMANDS", "true");
+        let guild = format!(
+            "/applications/{}/guilds/{}/commands",
+            fixtures::APPLICATION_ID,
+            fixtures::GUILD_ID
+        );
+        harness.discord.respond("PUT", &guild, 200, "[]");
+
+        harness
+            .send(&fixtures::guild_command(
+                "config",
+                &[("setting", "disable_command"), ("value", "hey")],
+                ADMINISTRATOR,
+            ))
+            .await;
+        harness
+            .send(&fixtures::guild_command(
+                "config",
+                &[("setting", "language"), ("value", "ja")],
+                ADMINISTRATOR,
+            ))
+            .await;
+
+        let requests = harness.discord.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].path, guild);
+        assert_eq!(
+            command_names(&requests[0].body),
+            ["audit", "jisho", "stats", "xe"]
+        );
+    })
+}
+
+#[test]
+fn the_guild_color_leaves_error_embeds_alone() {
+    run(async {
+        let harness = Harness::new();
+        GuildConfig {
+            embed_color: Some(0x123456),
+            ..GuildConfig::default()
+        }
+        .save(harness.store(), fixtures::GUILD_ID)
+        .await
+        .unwrap();
+        harness
+            .jisho
+            .respond("GET", "/search/words", 200, fixtures::JISHO_EMPTY);
+        harness
+            .fixer
+            .respond("GET", "/latest", 200, fixtures::FIXER_LATEST_AUD_JPY);
+
+        let jisho = harness
+            .send(&fixtures::guild_command(
+                "jisho",
+                &[("word", "zzz")],
+                MEMBER,
+            ))
+            .await;
+        let xe = harness
+            .send(&fixtures::guild_command(
+                "xe",
+                &[("from", "AUD"), ("to", "JPY"), ("amount", "50")],
+                MEMBER,
+            ))
+            .await;
+
+        assert_eq!(json(&jisho)["data"]["embeds"][0]["title"], "Error");
+        assert_eq!(json(&jisho)["data"]["embeds"][0]["color"], 0xFF0000);
+        assert_eq!(json(&xe)["data"]["embeds"][0]["color"], 0x123456);
+    })
+}
+
+#[test]
+fn xe_set_defaults_keeps_only_the_given_options() {
+    run(async {
+        let harness = Harness::new();
+        GuildConfig {
+            currency_from: Some("USD".into()),
+            currency_to: Some("JPY".into()),
+            ..GuildConfig::default()
+        }
+        .save(harness.store(), fixtures::GUILD_ID)
+        .await
+        .unwrap();
+        harness
+            .fixer
+            .respond("GET", "/latest", 200, fixtures::FIXER_LATEST_AUD_JPY);
+
+        let response = harness
+            .send(&fixtures::guild_command(
+                "xe",
+                &[("from", "AUD"), ("amount", "50"), ("set_defaults", "True")],
+                MEMBER,
+            ))
+            .await;
+
+        assert_eq!(response.status, 200);
+        let saved = UserPreferences::load(harness.store(), fixtures::USER_ID, "loki")
+            .await
+            .unwrap();
+        assert_eq!(
+            saved,
+            UserPreferences {
+                currency_from: Some("AUD".into()),
+                ..UserPreferences::default()
+            }
+        );
+    })
+}
+
+#[test]
+fn xe_set_defaults_works_without_a_fixer_token() {
+    run(async {
+        let mut harness = Harness::new();
+        harness.set_var("CURR_CONV_TOKEN", "");
+
+        let saved = harness
+            .send(&fixtures::guild_command(
+                "xe",
+                &[("from", "AUD"), ("set_defaults", "True")],
+                MEMBER,
+            ))
+            .await;
+        let converted = harness
+            .send(&fixtures::guild_command("xe", &[("from", "AUD")], MEMBER))
+            .await;
+
+        assert_eq!(saved.status, 200);
+        assert_eq!(
+            json(&saved)["data"]["embeds"][0]["description"],
+            "Defaults have been updated"
+        );
+        // Converting still needs the token
+        assert_eq!(converted.status, 500);
+        assert!(harness.fixer.requests().is_empty());
+    })
+}
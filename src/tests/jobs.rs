@@ -0,0 +1,64 @@
+use crate::job::{self, JobRun};
+use crate::routes;
+
+use super::fixtures;
+use super::{json, run, Harness};
+
+const EVERY_SIX_HOURS: &str = "0 */6 * * *";
+
+#[test]
+fn jobs_run_on_their_cron_and_record_the_run() {
+    run(async {
+        let mut harness = Harness::new();
+        harness.set_var(
+            "JOB_SCHEDULE",
+            &format!("register_commands={}", EVERY_SIX_HOURS),
+        );
+        let path = format!("/applications/{}/commands", fixtures::APPLICATION_ID);
+        harness.discord.respond("PUT", &path, 200, "[]");
+        let (ctx, _) = harness.context();
+
+        let unscheduled = job::run_scheduled(&ctx, "0 0 * * *").await;
+        let runs = job::run_scheduled(&ctx, EVERY_SIX_HOURS).await;
+
+        assert!(unscheduled.is_empty());
+        assert_eq!(runs.len(), 1);
+        assert!(runs[0].succeeded());
+        assert_eq!(harness.discord.requests().len(), 1);
+        let recorded = JobRun::load(harness.store(), "register_commands")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(recorded.trigger, EVERY_SIX_HOURS);
+        assert_eq!(recorded.error, None);
+    })
+}
+
+#[test]
+fn jobs_can_be_run_by_hand_with_the_admin_token() {
+    run(async {
+        let mut harness = Harness::new();
+
+        let unconfigured =
+            routes::run_job(harness.context().0, "register_commands", Some("Bearer ")).await;
+        harness.set_var("ADMIN_TOKEN", "admin-token");
+        let unknown = routes::run_job(harness.context().0, "nap", Some("Bearer admin-token")).await;
+        // Discord has no route for the registration, so the job fails
+        let failed = routes::run_job(
+            harness.context().0,
+            "register_commands",
+            Some("Bearer admin-token"),
+        )
+        .await;
+
+        assert_eq!(unconfigured.status, 401);
+        assert_eq!(unknown.status, 404);
+        assert_eq!(failed.status, 500);
+        let run = json(&failed);
+        assert_eq!(run["trigger"], "manual");
+        assert!(run["error"].as_str().unwrap().contains("404"));
+        let diagnostics =
+            json(&routes::diagnostics(harness.context().0, Some("Bearer admin-token")).await);
+        assert_eq!(diagnostics["jobs"]["register_commands"], run);
+    })
+}
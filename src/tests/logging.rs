@@ -0,0 +1,106 @@
+use serde_json::json;
+
+use crate::logger::{self, Format, Level};
+
+use super::fixtures::{self, GUILD_ID, MEMBER};
+use super::{run, Harness, LogLines};
+
+#[test]
+fn interactions_are_logged_as_one_event() {
+    run(async {
+        let logs = LogLines::capture();
+        let harness = Harness::new();
+        harness
+            .jisho
+            .respond("GET", "/search/words", 200, fixtures::JISHO_NEKO);
+        let interaction = fixtures::guild_command("jisho", &[("word", "neko")], MEMBER);
+
+        harness.send(&interaction).await;
+        harness
+            .send(&fixtures::guild_command(
+                "jisho",
+                &[("word", "neko")],
+                MEMBER,
+            ))
+            .await;
+
+        let events = logs.events("interaction");
+        assert_eq!(events.len(), 2);
+        let first = &events[0];
+        assert_eq!(first["level"], "info");
+        assert_eq!(first["interaction_id"], interaction["id"]);
+        assert_eq!(first["type"], "command");
+        assert_eq!(first["command"], "jisho");
+        assert_eq!(first["guild_id"], GUILD_ID);
+        assert_eq!(first["outcome"], "answered");
+        assert!(first["duration_ms"].is_i64());
+        assert_eq!(first["cache"]["jisho"]["misses"], 1);
+        assert_eq!(first["upstream"][0]["upstream"], "jisho");
+        assert_eq!(first["upstream"][0]["status"], 200);
+        assert_eq!(first["upstream"][0]["attempts"], 1);
+        assert!(first["upstream"][0]["latency_ms"].is_u64());
+
+        assert_eq!(events[1]["cache"]["jisho"]["hits"], 1);
+        assert_eq!(events[1]["upstream"], json!([]));
+    })
+}
+
+#[test]
+fn unreachable_upstreams_are_logged_as_warnings() {
+    run(async {
+        let logs = LogLines::capture();
+        let harness = Harness::new();
+        harness.jisho.respond("GET", "/search/words", 503, "{}");
+
+        harness
+            .send(&fixtures::guild_command(
+                "jisho",
+                &[("word", "neko")],
+                MEMBER,
+            ))
+            .await;
+
+        let event = &logs.events("interaction")[0];
+        assert_eq!(event["level"], "warn");
+        assert_eq!(event["outcome"], "upstream_unavailable");
+        let call = &event["upstream"][0];
+        assert_eq!(call["status"], 503);
+        assert_eq!(call["attempts"], harness.jisho.requests().len());
+    })
+}
+
+#[test]
+fn rejected_requests_are_logged() {
+    run(async {
+        let logs = LogLines::capture();
+        let harness = Harness::new();
+
+        harness
+            .send_raw(
+                &[("content-type", "application/json")],
+                fixtures::ping().to_string(),
+            )
+            .await;
+
+        let event = &logs.events("interaction")[0];
+        assert_eq!(event["outcome"], "rejected");
+        assert_eq!(event.get("interaction_id"), None);
+    })
+}
+
+#[test]
+fn interaction_tokens_and_users_stay_out_of_the_logs() {
+    run(async {
+        logger::init(Level::Debug, Format::Json, Vec::new());
+        let logs = LogLines::capture();
+        let harness = Harness::new();
+        let interaction = fixtures::guild_command("hey", &[("name", "Loki")], MEMBER);
+
+        harness.send(&interaction).await;
+
+        let logged = serde_json::to_string(&logs.all()).unwrap();
+        assert!(logs.all().len() > 1);
+        assert!(!logged.contains(interaction["token"].as_str().unwrap()));
+        assert!(!logged.contains(fixtures::USER_ID));
+    })
+}
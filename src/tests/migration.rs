@@ -0,0 +1,256 @@
+use std::cell::RefCell;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::cache;
+use crate::preferences::{TimeseriesOffset, UserPreferences};
+use crate::store::migration::{migrate, read_record, Record};
+use crate::store::{ListPage, MemoryStore, Store, StoreError};
+
+use super::fixtures;
+use super::{run, Harness};
+
+fn preferences_key(user_id: &str) -> String {
+    format!("user_preferences:{}", user_id)
+}
+
+async fn stored(store: &dyn Store, key: &str) -> Value {
+    serde_json::from_str(&store.get(key).await.unwrap().unwrap()).unwrap()
+}
+
+/// Preferences as version 1 stored them, with the offset as a string.
+fn preferences_v1() -> String {
+    json!({
+        "version": 1,
+        "currency_from": "AUD",
+        "currency_to": "JPY",
+        "precision": 2,
+        "timeseries_offset": "-7_0",
+    })
+    .to_string()
+}
+
+fn upgraded_preferences() -> UserPreferences {
+    UserPreferences {
+        currency_from: Some("AUD".into()),
+        currency_to: Some("JPY".into()),
+        precision: Some(2),
+        timeseries_offset: Some(TimeseriesOffset { start: -7, end: 0 }),
+    }
+}
+
+#[test]
+fn outdated_records_are_upgraded_as_they_are_read() {
+    run(async {
+        let harness = Harness::new();
+        let key = preferences_key(fixtures::USER_ID);
+        harness
+            .store()
+            .put(&key, &preferences_v1(), None)
+            .await
+            .unwrap();
+
+        let read = read_record::<UserPreferences>(harness.store(), &key)
+            .await
+            .unwrap();
+
+        assert_eq!(read, Some(upgraded_preferences()));
+        // Reading leaves the stored shape alone, only saving or migrating rewrites it
+        assert_eq!(stored(harness.store(), &key).await["version"], 1);
+    })
+}
+
+#[test]
+fn migrate_rewrites_outdated_records() {
+    run(async {
+        let harness = Harness::new();
+        let store = harness.store();
+        store
+            .put(&preferences_key("1"), &preferences_v1(), None)
+            .await
+            .unwrap();
+        upgraded_preferences().save(store, "2").await.unwrap();
+        // Preferences were versioned from the start, so there is no upgrade from 0
+        store
+            .put(&preferences_key("3"), r#"{"currency_from": "AUD"}"#, None)
+            .await
+            .unwrap();
+        store.put(&preferences_key("4"), "AUD", None).await.unwrap();
+
+        let report = migrate::<UserPreferences>(store).await.unwrap();
+
+        assert_eq!(report.scanned, 4);
+        assert_eq!(report.upgraded, 1);
+        assert_eq!(report.deleted, 0);
+        assert_eq!(report.failed, [preferences_key("3"), preferences_key("4")]);
+        let rewritten = stored(store, &preferences_key("1")).await;
+        assert_eq!(rewritten["version"], 2);
+        assert_eq!(
+            rewritten["timeseries_offset"],
+            json!({ "start": -7, "end": 0 })
+        );
+        assert_eq!(
+            read_record::<UserPreferences>(store, &preferences_key("1"))
+                .await
+                .unwrap(),
+            Some(upgraded_preferences())
+        );
+        // Running again finds nothing left to do
+        let again = migrate::<UserPreferences>(store).await.unwrap();
+        assert_eq!((again.scanned, again.upgraded), (4, 0));
+    })
+}
+
+#[test]
+fn migrate_deletes_outdated_cache_entries() {
+    run(async {
+        let harness = Harness::new();
+        let store = harness.store();
+        store
+            .put("cached:rates:AUD", r#"{"stored_at": 0, "value": 1}"#, None)
+            .await
+            .unwrap();
+        store
+            .put(
+                "cached:rates:JPY",
+                r#"{"version": 1, "stored_at": 0, "value": 1}"#,
+                None,
+            )
+            .await
+            .unwrap();
+
+        let report = cache::migrate_entries(store).await.unwrap();
+
+        assert_eq!((report.scanned, report.deleted), (2, 1));
+        assert_eq!(store.get("cached:rates:AUD").await.unwrap(), None);
+        assert!(store.get("cached:rates:JPY").await.unwrap().is_some());
+    })
+}
+
+#[test]
+fn legacy_defaults_are_moved_to_a_record_once() {
+    run(async {
+        let harness = Harness::new();
+        let store = harness.store();
+        for (suffix, value) in [
+            ("currency_from", "AUD"),
+            ("currency_to", "JPY"),
+            ("currency_precision", "2"),
+            ("timeseries_offset", "-7_0"),
+        ]
+        .iter()
+        {
+            store
+                .put(&format!("loki:{}", suffix), value, None)
+                .await
+                .unwrap();
+        }
+
+        let loaded = UserPreferences::load(store, fixtures::USER_ID, "loki")
+            .await
+            .unwrap();
+        let someone_else = UserPreferences::load(store, "5", "loki").await.unwrap();
+
+        let expected = upgraded_preferences();
+        assert_eq!(loaded, expected);
+        assert_eq!(someone_else, UserPreferences::default());
+        assert_eq!(store.get("loki:currency_from").await.unwrap(), None);
+        assert_eq!(
+            read_record::<UserPreferences>(store, &preferences_key(fixtures::USER_ID))
+                .await
+                .unwrap(),
+            Some(expected)
+        );
+    })
+}
+
+#[test]
+fn users_without_legacy_defaults_are_only_checked_once() {
+    run(async {
+        let harness = Harness::new();
+        let store = harness.store();
+
+        let first = UserPreferences::load(store, fixtures::USER_ID, "freya")
+            .await
+            .unwrap();
+        // Keys showing up later are not looked for again
+        store.put("freya:currency_from", "AUD", None).await.unwrap();
+        let second = UserPreferences::load(store, fixtures::USER_ID, "freya")
+            .await
+            .unwrap();
+
+        assert_eq!(first, UserPreferences::default());
+        assert_eq!(second, UserPreferences::default());
+        assert!(store.get("freya:currency_from").await.unwrap().is_some());
+    })
+}
+
+/// A [`MemoryStore`] that remembers the TTL of every write.
+#[derive(Default)]
+struct TtlStore {
+    inner: MemoryStore,
+    ttls: RefCell<Vec<(String, Option<u64>)>>,
+}
+
+#[async_trait(?Send)]
+impl Store for TtlStore {
+    async fn get(&self, key: &str) -> Result<Option<String>, StoreError> {
+        self.inner.get(key).await
+    }
+
+    async fn put(&self, key: &str, value: &str, ttl: Option<u64>) -> Result<(), StoreError> {
+        self.ttls.borrow_mut().push((key.into(), ttl));
+        self.inner.put(key, value, ttl).await
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StoreError> {
+        self.inner.delete(key).await
+    }
+
+    async fn list(&self, prefix: &str, cursor: Option<String>) -> Result<ListPage, StoreError> {
+        self.inner.list(prefix, cursor).await
+    }
+}
+
+/// A record kept for an hour, whose `count` was called `total` in version 1.
+#[derive(Deserialize, Serialize)]
+struct Expiring {
+    count: u32,
+}
+
+impl Record for Expiring {
+    const KIND: &'static str = "expiring";
+    const PREFIX: &'static str = "expiring:";
+    const VERSION: u32 = 2;
+    const TTL: Option<u64> = Some(60 * 60);
+
+    fn upgrade(version: u32, value: Value) -> Result<Value, StoreError> {
+        match version {
+            1 => Ok(json!({ "count": value["total"] })),
+            _ => Err(StoreError::UnsupportedVersion(Self::KIND.into(), version)),
+        }
+    }
+}
+
+#[test]
+fn migrate_keeps_the_expiration_of_records() {
+    run(async {
+        let store = TtlStore::default();
+        store
+            .inner
+            .put("expiring:1", r#"{"version": 1, "total": 3}"#, Some(60))
+            .await
+            .unwrap();
+
+        let report = migrate::<Expiring>(&store).await.unwrap();
+
+        assert_eq!(report.upgraded, 1);
+        assert_eq!(
+            *store.ttls.borrow(),
+            [("expiring:1".to_string(), Some(60 * 60))]
+        );
+        assert_eq!(stored(&store, "expiring:1").await["count"], 3);
+    })
+}
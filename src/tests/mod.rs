@@ -0,0 +1,204 @@
+//! End-to-end tests. Interactions are signed the way Discord signs them and
+//! fed through [`App::handle_request`], with jisho, fixer and Discord served
+//! by local stand-ins and data kept in a [`MemoryStore`].
+
+mod analytics;
+mod audit;
+mod config;
+mod error_report;
+mod fixtures;
+mod guild_config;
+mod interactions;
+mod jobs;
+mod logging;
+mod migration;
+mod stand_in;
+mod status;
+mod upstream;
+mod user_data;
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::future::Future;
+use std::rc::Rc;
+
+use ed25519_dalek::{Keypair, Signer};
+use rand::rngs::OsRng;
+use serde_json::Value;
+
+use crate::bot::{App, IncomingRequest};
+use crate::config::Config;
+use crate::context::{Background, CommandContext};
+use crate::http::HttpResponse;
+use crate::logger::{self, Level, Sink};
+use crate::routes;
+use crate::store::{MemoryStore, Store};
+use crate::upstream::HttpClient;
+use stand_in::StandIn;
+
+pub(crate) struct Harness {
+    keypair: Keypair,
+    store: Rc<MemoryStore>,
+    vars: HashMap<String, String>,
+    http: Option<Rc<dyn HttpClient>>,
+    pub(crate) jisho: StandIn,
+    pub(crate) fixer: StandIn,
+    pub(crate) discord: StandIn,
+}
+
+impl Harness {
+    /// A bot with a fresh signing key, an empty store and every upstream
+    /// pointed at a stand-in that has no routes yet.
+    pub(crate) fn new() -> Self {
+        let keypair = Keypair::generate(&mut OsRng);
+        let (jisho, fixer, discord) = (StandIn::start(), StandIn::start(), StandIn::start());
+        let vars = [
+            ("DISCORD_PUBLIC_KEY", hex::encode(keypair.public.to_bytes())),
+            ("DISCORD_APPLICATION_ID", fixtures::APPLICATION_ID.into()),
+            ("DISCORD_TOKEN", "bot-token".into()),
+            ("DISCORD_API_BASE", discord.url()),
+            ("CURR_CONV_TOKEN", "fixer-token".into()),
+            ("FIXER_API_BASE", fixer.url()),
+            ("JISHO_API_BASE", jisho.url()),
+        ]
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.clone()))
+        .collect();
+
+        Self {
+            keypair,
+            store: Rc::new(MemoryStore::new()),
+            vars,
+            http: None,
+            jisho,
+            fixer,
+            discord,
+        }
+    }
+
+    pub(crate) fn set_var(&mut self, key: &str, value: &str) {
+        self.vars.insert(key.into(), value.into());
+    }
+
+    /// Sends upstream requests through `http` rather than to the stand-ins.
+    pub(crate) fn set_http(&mut self, http: Rc<dyn HttpClient>) {
+        self.http = Some(http);
+    }
+
+    pub(crate) fn store(&self) -> &dyn Store {
+        &*self.store
+    }
+
+    /// A context like the one a server builds for each request.
+    pub(crate) fn context(&self) -> (CommandContext, Background) {
+        let var = |key: &str| self.vars.get(key).cloned();
+        let config = Config::load(var, var).expect("the harness configuration is valid");
+        let background = Background::default();
+        let mut ctx = CommandContext::new(self.store.clone(), Rc::new(config), background.clone());
+        if let Some(http) = &self.http {
+            ctx = ctx.with_http(http.clone());
+        }
+        (ctx, background)
+    }
+
+    /// The hex encoded signature Discord would send for `body` at `timestamp`.
+    pub(crate) fn sign(&self, timestamp: &str, body: &str) -> String {
+        let message = format!("{}{}", timestamp, body);
+        hex::encode(self.keypair.sign(message.as_bytes()).to_bytes())
+    }
+
+    /// Signs and sends an interaction, then runs any background work as the
+    /// servers do once the response is out.
+    pub(crate) async fn send(&self, interaction: &Value) -> HttpResponse {
+        let body = interaction.to_string();
+        let timestamp = chrono::Utc::now().timestamp().to_string();
+        let signature = self.sign(&timestamp, &body);
+        self.send_raw(
+            &[
+                ("content-type", "application/json"),
+                ("x-signature-ed25519", &signature),
+                ("x-signature-timestamp", &timestamp),
+            ],
+            body,
+        )
+        .await
+    }
+
+    /// Sends `body` with exactly the given headers.
+    pub(crate) async fn send_raw(&self, headers: &[(&str, &str)], body: String) -> HttpResponse {
+        let headers = headers
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        let req = IncomingRequest::new("POST", headers, async move { Ok(body.into_bytes()) });
+        let (ctx, background) = self.context();
+
+        let response = HttpResponse::interaction(App::new(req, ctx).handle_request().await);
+        background.run().await;
+        response
+    }
+
+    /// Calls `POST /register`.
+    pub(crate) async fn register(&self) -> HttpResponse {
+        let (ctx, background) = self.context();
+        let response = routes::register(ctx).await;
+        background.run().await;
+        response
+    }
+}
+
+/// Keeps log lines written on the current test's thread, parsed as JSON.
+#[derive(Default)]
+pub(crate) struct LogLines {
+    lines: RefCell<Vec<Value>>,
+}
+
+impl LogLines {
+    /// Sends log lines here instead of standard error for the rest of the test.
+    pub(crate) fn capture() -> Rc<Self> {
+        let lines = Rc::new(LogLines::default());
+        logger::set_sink(Some(lines.clone()));
+        lines
+    }
+
+    pub(crate) fn events(&self, name: &str) -> Vec<Value> {
+        self.lines
+            .borrow()
+            .iter()
+            .filter(|line| line["event"] == name)
+            .cloned()
+            .collect()
+    }
+
+    pub(crate) fn all(&self) -> Vec<Value> {
+        self.lines.borrow().clone()
+    }
+}
+
+impl Sink for LogLines {
+    fn write(&self, _level: Level, line: &str) {
+        let line = serde_json::from_str(line)
+            .unwrap_or_else(|_| panic!("expected a JSON log line, got {}", line));
+        self.lines.borrow_mut().push(line);
+    }
+}
+
+/// Runs a test on a single-threaded runtime, as command futures are not `Send`.
+pub(crate) fn run<F: Future>(test: F) -> F::Output {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("build a runtime");
+    tokio::task::LocalSet::new().block_on(&runtime, test)
+}
+
+/// The body of a JSON response.
+pub(crate) fn json(response: &HttpResponse) -> Value {
+    serde_json::from_slice(&response.body).unwrap_or_else(|_| {
+        panic!(
+            "expected JSON, got {} {}",
+            response.status,
+            String::from_utf8_lossy(&response.body)
+        )
+    })
+}
@@ -0,0 +1,178 @@
+//! A minimal HTTP server standing in for an upstream API, answering with
+//! canned responses and recording every request it receives.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+struct Route {
+    method: String,
+    path: String,
+    status: u16,
+    body: String,
+    /// Answers a single request, then gives way to the next matching route.
+    once: bool,
+}
+
+/// A request as received by a [`StandIn`].
+#[derive(Clone, Debug)]
+pub(crate) struct Recorded {
+    pub(crate) method: String,
+    /// The path along with the query string.
+    pub(crate) path: String,
+    /// Header names are lowercased.
+    pub(crate) headers: HashMap<String, String>,
+    pub(crate) body: String,
+}
+
+pub(crate) struct StandIn {
+    address: SocketAddr,
+    routes: Arc<Mutex<Vec<Route>>>,
+    requests: Arc<Mutex<Vec<Recorded>>>,
+    delay: Arc<Mutex<Duration>>,
+}
+
+impl StandIn {
+    /// Listens on a free local port until the test process exits.
+    pub(crate) fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind a local port");
+        let address = listener.local_addr().expect("local address");
+        let routes = Arc::new(Mutex::new(Vec::<Route>::new()));
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let delay = Arc::new(Mutex::new(Duration::default()));
+
+        let (served_routes, served_requests, served_delay) =
+            (routes.clone(), requests.clone(), delay.clone());
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let (routes, requests, delay) = (
+                    served_routes.clone(),
+                    served_requests.clone(),
+                    served_delay.clone(),
+                );
+                // Slow responses must not hold up the requests behind them
+                thread::spawn(move || {
+                    if let Some(request) = read_request(&stream) {
+                        let (status, body) = answer(&mut routes.lock().unwrap(), &request);
+                        requests.lock().unwrap().push(request);
+                        let delay = *delay.lock().unwrap();
+                        thread::sleep(delay);
+                        write_response(stream, status, &body);
+                    }
+                });
+            }
+        });
+
+        Self {
+            address,
+            routes,
+            requests,
+            delay,
+        }
+    }
+
+    /// The root URL to hand to the client under test.
+    pub(crate) fn url(&self) -> String {
+        format!("http://{}", self.address)
+    }
+
+    /// Answers `method` requests to `path`, ignoring the query string, with
+    /// `status` and a JSON `body`.
+    pub(crate) fn respond(&self, method: &str, path: &str, status: u16, body: impl Into<String>) {
+        self.add_route(method, path, status, body.into(), false);
+    }
+
+    /// Like [`StandIn::respond`], for the next matching request only.
+    pub(crate) fn respond_once(
+        &self,
+        method: &str,
+        path: &str,
+        status: u16,
+        body: impl Into<String>,
+    ) {
+        self.add_route(method, path, status, body.into(), true);
+    }
+
+    fn add_route(&self, method: &str, path: &str, status: u16, body: String, once: bool) {
+        self.routes.lock().unwrap().push(Route {
+            method: method.into(),
+            path: path.into(),
+            status,
+            body,
+            once,
+        });
+    }
+
+    /// Waits this long before sending every response from now on.
+    pub(crate) fn delay_responses(&self, delay: Duration) {
+        *self.delay.lock().unwrap() = delay;
+    }
+
+    /// Every request received so far, oldest first.
+    pub(crate) fn requests(&self) -> Vec<Recorded> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+/// The first route matching `request`, ignoring the query string.
+fn answer(routes: &mut Vec<Route>, request: &Recorded) -> (u16, String) {
+    let path = request.path.split('?').next().unwrap_or_default();
+    match routes
+        .iter()
+        .position(|route| route.method == request.method && route.path == path)
+    {
+        Some(index) if routes[index].once => {
+            let route = routes.remove(index);
+            (route.status, route.body)
+        }
+        Some(index) => (routes[index].status, routes[index].body.clone()),
+        None => (404, r#"{"message": "404: Not Found"}"#.into()),
+    }
+}
+
+fn read_request(stream: &TcpStream) -> Option<Recorded> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line).ok()?;
+    let mut parts = line.split_whitespace();
+    let (method, path) = (parts.next()?.to_string(), parts.next()?.to_string());
+
+    let mut headers = HashMap::new();
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).ok()?;
+        match line.trim_end().split_once(':') {
+            Some((key, value)) => {
+                headers.insert(key.trim().to_lowercase(), value.trim().to_string());
+            }
+            None => break,
+        }
+    }
+
+    let length = headers
+        .get("content-length")
+        .and_then(|length| length.parse::<usize>().ok())
+        .unwrap_or(0);
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).ok()?;
+
+    Some(Recorded {
+        method,
+        path,
+        headers,
+        body: String::from_utf8_lossy(&body).into_owned(),
+    })
+}
+
+fn write_response(mut stream: TcpStream, status: u16, body: &str) {
+    let response = format!(
+        "HTTP/1.1 {} Stand-in\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    let _ = stream.write_all(response.as_bytes());
+}
@@ -0,0 +1,94 @@
+use serde_json::json;
+
+use crate::circuit_breaker::CircuitState;
+use crate::routes;
+use crate::upstream::Upstream;
+
+use super::fixtures::{self, MEMBER};
+use super::{json, run, Harness};
+
+#[test]
+fn health_reports_the_store() {
+    run(async {
+        let harness = Harness::new();
+
+        let response = routes::health(harness.context().0).await;
+
+        assert_eq!(response.status, 200);
+        assert_eq!(json(&response), json!({ "status": "ok", "store": "ok" }));
+    })
+}
+
+#[test]
+fn version_lists_every_command_with_a_hash() {
+    let response = routes::version();
+
+    let version = json(&response);
+    assert_eq!(version["version"], env!("CARGO_PKG_VERSION"));
+    assert!(!version["commit"].as_str().unwrap().is_empty());
+    let commands = version["commands"].as_array().unwrap();
+    assert_eq!(commands.len(), 7);
+    assert_eq!(commands[3]["name"], "jisho");
+    assert!(commands
+        .iter()
+        .all(|command| command["hash"].as_str().unwrap().len() == 64));
+    assert_eq!(json(&routes::version()), version);
+}
+
+#[test]
+fn diagnostics_need_the_admin_token() {
+    run(async {
+        let mut harness = Harness::new();
+
+        let unconfigured = routes::diagnostics(harness.context().0, Some("Bearer ")).await;
+        harness.set_var("ADMIN_TOKEN", "admin-token");
+        let wrong = routes::diagnostics(harness.context().0, Some("Bearer nope")).await;
+        let missing = routes::diagnostics(harness.context().0, None).await;
+
+        assert_eq!(unconfigured.status, 401);
+        assert_eq!(wrong.status, 401);
+        assert_eq!(missing.status, 401);
+    })
+}
+
+#[test]
+fn diagnostics_report_vars_circuits_and_cache_lookups() {
+    run(async {
+        let mut harness = Harness::new();
+        harness.set_var("ADMIN_TOKEN", "admin-token");
+        harness
+            .jisho
+            .respond("GET", "/search/words", 200, fixtures::JISHO_NEKO);
+        harness
+            .send(&fixtures::guild_command(
+                "jisho",
+                &[("word", "neko")],
+                MEMBER,
+            ))
+            .await;
+        let now = chrono::Utc::now().timestamp();
+        let mut circuit = CircuitState::default();
+        for _ in 0..5 {
+            circuit = circuit
+                .record_failure(harness.store(), Upstream::Fixer, now)
+                .await
+                .unwrap();
+        }
+
+        let response = routes::diagnostics(harness.context().0, Some("Bearer admin-token")).await;
+
+        assert_eq!(response.status, 200);
+        let diagnostics = json(&response);
+        let set = diagnostics["vars"]["set"].as_array().unwrap();
+        assert!(set.contains(&json!("DISCORD_TOKEN")));
+        assert!(diagnostics["vars"]["unset"]
+            .as_array()
+            .unwrap()
+            .contains(&json!("AUDIT_WEBHOOK_URL")));
+        assert!(!diagnostics.to_string().contains("bot-token"));
+        assert_eq!(diagnostics["circuits"]["fixer"]["open"], true);
+        assert_eq!(diagnostics["circuits"]["fixer"]["failures"], 5);
+        assert_eq!(diagnostics["circuits"]["jisho"]["open"], false);
+        assert_eq!(diagnostics["cache"]["jisho"]["misses"], 1);
+    })
+}
@@ -0,0 +1,387 @@
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+
+use crate::circuit_breaker::CircuitState;
+use crate::store::migration::write_record;
+use crate::store::{MemoryStore, Store};
+use crate::upstream::recording::{Recorder, Replayer};
+use crate::upstream::{
+    HttpClient, LiveClient, RetryPolicy, Upstream, UpstreamClient, UpstreamError,
+};
+
+use super::fixtures::{self, MEMBER};
+use super::{json, run, Harness};
+
+fn recorded() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/tests/fixtures/upstream")
+}
+
+fn jisho_client(http: Rc<dyn HttpClient>, store: Rc<dyn Store>, url: String) -> UpstreamClient {
+    UpstreamClient::new(http, store, Upstream::Jisho, url)
+}
+
+/// A directory of its own for each test that records.
+fn scratch_dir() -> PathBuf {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    std::env::temp_dir().join(format!(
+        "nuko-recording-{}-{}",
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    ))
+}
+
+#[test]
+fn jisho_replays_recorded_responses() {
+    run(async {
+        let mut harness = Harness::new();
+        harness.set_http(Rc::new(Replayer::new(recorded())));
+
+        let response = harness
+            .send(&fixtures::guild_command(
+                "jisho",
+                &[("word", "inu")],
+                MEMBER,
+            ))
+            .await;
+
+        let fields = &json(&response)["data"]["embeds"][0]["fields"];
+        assert_eq!(fields[1]["value"], "dog");
+        assert_eq!(fields[2]["value"], "犬");
+        assert!(harness.jisho.requests().is_empty());
+    })
+}
+
+#[test]
+fn recorded_responses_replay_without_the_upstream() {
+    run(async {
+        let dir = scratch_dir();
+        let interaction = || {
+            fixtures::guild_command(
+                "xe",
+                &[("from", "AUD"), ("to", "JPY"), ("amount", "50")],
+                MEMBER,
+            )
+        };
+
+        let mut recording = Harness::new();
+        recording
+            .fixer
+            .respond("GET", "/latest", 200, fixtures::FIXER_LATEST_AUD_JPY);
+        recording.set_http(Rc::new(Recorder::new(
+            Rc::new(LiveClient::new()),
+            dir.clone(),
+        )));
+        let recorded = json(&recording.send(&interaction()).await);
+
+        let recording_file = dir.join("fixer/GET_latest_symbols_JPY_base_AUD.json");
+        let saved = std::fs::read_to_string(&recording_file).unwrap();
+        assert!(!saved.contains("fixer-token"));
+
+        let mut replaying = Harness::new();
+        replaying.set_http(Rc::new(Replayer::new(dir.clone())));
+        let replayed = json(&replaying.send(&interaction()).await);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            recorded["data"]["embeds"][0]["description"],
+            "50 AUD --> 4712.6500 JPY"
+        );
+        assert_eq!(replayed, recorded);
+        assert!(replaying.fixer.requests().is_empty());
+    })
+}
+
+#[test]
+fn unrecorded_requests_fail_when_replaying() {
+    run(async {
+        let client = jisho_client(
+            Rc::new(Replayer::new(recorded())),
+            Rc::new(MemoryStore::new()),
+            "http://localhost".into(),
+        );
+
+        let result = client
+            .get_json::<Value>("/search/words?keyword=never", &[])
+            .await;
+
+        assert!(matches!(
+            result,
+            Err(UpstreamError::NotRecorded(Upstream::Jisho, _))
+        ));
+    })
+}
+
+#[test]
+fn error_statuses_are_reported() {
+    run(async {
+        let harness = Harness::new();
+        let client = jisho_client(
+            Rc::new(LiveClient::new()),
+            Rc::new(MemoryStore::new()),
+            harness.jisho.url(),
+        );
+
+        let result = client
+            .get_json::<Value>("/search/words?keyword=neko", &[])
+            .await;
+
+        assert!(matches!(
+            result,
+            Err(UpstreamError::Status(Upstream::Jisho, 404, _))
+        ));
+    })
+}
+
+#[test]
+fn client_errors_are_not_retried() {
+    run(async {
+        let harness = Harness::new();
+        let client = jisho_client(
+            Rc::new(LiveClient::new()),
+            Rc::new(MemoryStore::new()),
+            harness.jisho.url(),
+        );
+
+        let _ = client.get_json::<Value>("/search/words", &[]).await;
+
+        assert_eq!(harness.jisho.requests().len(), 1);
+    })
+}
+
+#[test]
+fn failed_gets_are_retried() {
+    run(async {
+        let harness = Harness::new();
+        harness
+            .jisho
+            .respond_once("GET", "/search/words", 503, "{}");
+        harness
+            .jisho
+            .respond_once("GET", "/search/words", 502, "{}");
+        harness
+            .jisho
+            .respond("GET", "/search/words", 200, fixtures::JISHO_EMPTY);
+        let store = Rc::new(MemoryStore::new());
+        let client = jisho_client(
+            Rc::new(LiveClient::new()),
+            store.clone(),
+            harness.jisho.url(),
+        );
+
+        let result = client.get_json::<Value>("/search/words", &[]).await;
+
+        assert!(result.is_ok());
+        assert_eq!(harness.jisho.requests().len(), 3);
+        let circuit = CircuitState::load(&*store, Upstream::Jisho).await.unwrap();
+        assert_eq!(circuit.failures, 0);
+    })
+}
+
+#[test]
+fn slow_upstreams_time_out_within_the_budget() {
+    run(async {
+        let harness = Harness::new();
+        harness
+            .jisho
+            .respond("GET", "/search/words", 200, fixtures::JISHO_EMPTY);
+        harness.jisho.delay_responses(Duration::from_millis(1000));
+        let client = jisho_client(
+            Rc::new(LiveClient::new()),
+            Rc::new(MemoryStore::new()),
+            harness.jisho.url(),
+        )
+        .with_policy(RetryPolicy {
+            budget: Duration::from_millis(300),
+            attempt_timeout: Duration::from_millis(100),
+            max_attempts: 5,
+            base_delay: Duration::from_millis(10),
+        });
+
+        let started = Instant::now();
+        let result = client.get_json::<Value>("/search/words", &[]).await;
+
+        assert!(matches!(
+            result,
+            Err(UpstreamError::Timeout(Upstream::Jisho))
+        ));
+        assert!(started.elapsed() < Duration::from_millis(600));
+        assert!(harness.jisho.requests().len() > 1);
+    })
+}
+
+#[test]
+fn circuits_open_after_repeated_failures() {
+    run(async {
+        let harness = Harness::new();
+        harness.jisho.respond("GET", "/search/words", 503, "{}");
+        let client = jisho_client(
+            Rc::new(LiveClient::new()),
+            Rc::new(MemoryStore::new()),
+            harness.jisho.url(),
+        )
+        .with_policy(RetryPolicy {
+            max_attempts: 1,
+            ..Upstream::Jisho.retry_policy()
+        });
+
+        for _ in 0..5 {
+            let result = client.get_json::<Value>("/search/words", &[]).await;
+            assert!(matches!(result, Err(UpstreamError::Status(_, 503, _))));
+        }
+        let result = client.get_json::<Value>("/search/words", &[]).await;
+
+        assert!(matches!(
+            result,
+            Err(UpstreamError::CircuitOpen(Upstream::Jisho))
+        ));
+        assert_eq!(harness.jisho.requests().len(), 5);
+    })
+}
+
+#[test]
+fn successes_close_the_circuit() {
+    run(async {
+        let harness = Harness::new();
+        harness
+            .jisho
+            .respond("GET", "/search/words", 200, fixtures::JISHO_EMPTY);
+        let store = Rc::new(MemoryStore::new());
+        // Open until a second ago, so the next call is let through
+        let circuit = CircuitState {
+            failures: 7,
+            open_until: Some(chrono::Utc::now().timestamp() - 1),
+        };
+        write_record(&*store, "circuit:jisho", &circuit, None)
+            .await
+            .unwrap();
+        let client = jisho_client(
+            Rc::new(LiveClient::new()),
+            store.clone(),
+            harness.jisho.url(),
+        );
+
+        assert!(client.get_json::<Value>("/search/words", &[]).await.is_ok());
+        assert!(store.get("circuit:jisho").await.unwrap().is_none());
+    })
+}
+
+#[test]
+fn unreachable_upstreams_get_a_notice() {
+    run(async {
+        let harness = Harness::new();
+        harness.jisho.respond("GET", "/search/words", 503, "{}");
+
+        let response = harness
+            .send(&fixtures::guild_command(
+                "jisho",
+                &[("word", "neko")],
+                MEMBER,
+            ))
+            .await;
+
+        assert_eq!(
+            json(&response),
+            serde_json::json!({
+                "type": 4,
+                "data": {
+                    "content": "This service is not responding right now, please try again in a minute",
+                    "choices": null,
+                    "embeds": null,
+                    "flags": 64,
+                },
+            })
+        );
+    })
+}
+
+#[test]
+fn open_circuits_skip_the_upstream() {
+    run(async {
+        let harness = Harness::new();
+        harness
+            .jisho
+            .respond("GET", "/search/words", 200, fixtures::JISHO_NEKO);
+        CircuitState {
+            failures: 4,
+            open_until: None,
+        }
+        .record_failure(
+            harness.store(),
+            Upstream::Jisho,
+            chrono::Utc::now().timestamp(),
+        )
+        .await
+        .unwrap();
+
+        let response = harness
+            .send(&fixtures::guild_command(
+                "jisho",
+                &[("word", "neko")],
+                MEMBER,
+            ))
+            .await;
+
+        assert_eq!(json(&response)["data"]["flags"], 64);
+        assert!(harness.jisho.requests().is_empty());
+    })
+}
+
+#[test]
+fn stale_rates_are_served_while_the_circuit_is_open() {
+    run(async {
+        let mut harness = Harness::new();
+        // Cached an hour ago, past the fresh minute but well within the stale day
+        harness
+            .store()
+            .put(
+                "cached:xe_rate:AUD_JPY",
+                &serde_json::json!({
+                    "version": 1,
+                    "stored_at": chrono::Utc::now().timestamp() - 60 * 60,
+                    "value": 94.253,
+                })
+                .to_string(),
+                None,
+            )
+            .await
+            .unwrap();
+        harness.set_var("RATE_CACHE_TTL", "60");
+        harness.fixer.respond("GET", "/latest", 500, "{}");
+        let interaction = || {
+            fixtures::guild_command(
+                "xe",
+                &[("from", "AUD"), ("to", "JPY"), ("amount", "50")],
+                MEMBER,
+            )
+        };
+
+        // Each failed refresh is retried, then counts once towards the circuit
+        let mut responses = Vec::new();
+        for _ in 0..5 {
+            responses.push(json(&harness.send(&interaction()).await));
+        }
+        let calls = harness.fixer.requests().len();
+        let circuit = CircuitState::load(harness.store(), Upstream::Fixer)
+            .await
+            .unwrap();
+        let while_open = json(&harness.send(&interaction()).await);
+
+        for response in &responses {
+            assert_eq!(
+                response["data"]["embeds"][0]["description"],
+                "50 AUD --> 4712.6500 JPY"
+            );
+        }
+        assert_eq!(
+            calls,
+            5 * Upstream::Fixer.retry_policy().max_attempts as usize
+        );
+        assert!(circuit.is_open(chrono::Utc::now().timestamp()));
+        assert_eq!(while_open, responses[0]);
+        assert_eq!(harness.fixer.requests().len(), calls);
+    })
+}
@@ -0,0 +1,91 @@
+use crate::locale::{Language, Text};
+use crate::preferences::UserPreferences;
+use crate::user_data;
+
+use super::fixtures::{self, MEMBER};
+use super::{json, run, Harness};
+
+/// A harness where the user has set their `/xe` defaults once.
+async fn with_defaults() -> Harness {
+    let harness = Harness::new();
+    harness
+        .fixer
+        .respond("GET", "/latest", 200, fixtures::FIXER_LATEST_AUD_JPY);
+    harness
+        .send(&fixtures::guild_command(
+            "xe",
+            &[("from", "AUD"), ("to", "JPY"), ("set_defaults", "True")],
+            MEMBER,
+        ))
+        .await;
+    harness
+}
+
+#[test]
+fn mydata_shows_everything_stored() {
+    run(async {
+        let harness = with_defaults().await;
+
+        let response = harness.send(&fixtures::dm_command("mydata", &[])).await;
+
+        let embed = &json(&response)["data"]["embeds"][0];
+        assert_eq!(embed["description"], Language::En.text(Text::MyDataHelp));
+        let names = embed["fields"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|field| field["name"].as_str().unwrap())
+            .collect::<Vec<&str>>();
+        assert_eq!(names, ["audit_log", "preferences"]);
+    })
+}
+
+#[test]
+fn mydata_exports_everything_as_an_attachment() {
+    run(async {
+        let harness = with_defaults().await;
+
+        let response = harness
+            .send(&fixtures::dm_command("mydata", &[("action", "export")]))
+            .await;
+
+        assert_eq!(response.status, 200);
+        assert!(response.content_type.starts_with("multipart/form-data"));
+        let body = String::from_utf8_lossy(&response.body);
+        assert!(body.contains(&format!("nuko-data-{}.json", fixtures::USER_ID)));
+        assert!(body.contains(Language::En.text(Text::DataExported)));
+        assert!(body.contains(r#""currency_from": "AUD""#));
+        assert!(body.contains(r#""setting": "set_defaults""#));
+    })
+}
+
+#[test]
+fn mydata_deletes_everything_stored() {
+    run(async {
+        let harness = with_defaults().await;
+
+        let deleted = harness
+            .send(&fixtures::dm_command("mydata", &[("action", "delete")]))
+            .await;
+        let shown = harness.send(&fixtures::dm_command("mydata", &[])).await;
+
+        assert_eq!(
+            json(&deleted)["data"]["embeds"][0]["description"],
+            Language::En.text(Text::DataDeleted)
+        );
+        assert_eq!(
+            json(&shown)["data"]["embeds"][0]["description"],
+            Language::En.text(Text::NoData)
+        );
+        assert!(user_data::collect(harness.store(), fixtures::USER_ID)
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            UserPreferences::load(harness.store(), fixtures::USER_ID, "loki")
+                .await
+                .unwrap(),
+            UserPreferences::default()
+        );
+    })
+}
@@ -0,0 +1,554 @@
+//! HTTP access to the APIs the commands build on.
+//!
+//! Requests go through an [`HttpClient`], so they can be served live, recorded
+//! into fixture files, or replayed from them without a network. Each
+//! [`Upstream`] has a base URL that can be pointed elsewhere with a var, such
+//! as a stand-in server, and a [`RetryPolicy`] keeping its calls within the
+//! time Discord gives an interaction.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::circuit_breaker::CircuitState;
+use crate::config::Config;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::config::UpstreamMode;
+use crate::discord::DiscordClient;
+use crate::store::Store;
+use crate::utils::{random_u64, sleep, timeout};
+
+/// Discord fails an interaction that is not answered within three seconds.
+const INTERACTION_WINDOW: Duration = Duration::from_millis(3000);
+/// Left of the window for reading and writing the store and sending the answer.
+const RESPONSE_MARGIN: Duration = Duration::from_millis(750);
+/// How much of an error is kept with an [`UpstreamCall`].
+const MAX_ERROR_CHARS: usize = 300;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, strum_macros::Display)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub(crate) enum Upstream {
+    Jisho,
+    Fixer,
+    Discord,
+}
+
+impl Upstream {
+    pub(crate) const ALL: [Upstream; 3] = [Upstream::Jisho, Upstream::Fixer, Upstream::Discord];
+
+    pub(crate) fn default_base_url(self) -> &'static str {
+        match self {
+            Upstream::Jisho => "https://jisho.org/api/v1",
+            Upstream::Fixer => "https://api.apilayer.com/fixer",
+            Upstream::Discord => DiscordClient::DEFAULT_BASE_URL,
+        }
+    }
+
+    pub(crate) fn base_url_var(self) -> &'static str {
+        match self {
+            Upstream::Jisho => "JISHO_API_BASE",
+            Upstream::Fixer => "FIXER_API_BASE",
+            Upstream::Discord => "DISCORD_API_BASE",
+        }
+    }
+
+    /// How calls made through an [`UpstreamClient`] are retried. Discord is not
+    /// called that way, [`DiscordClient`] waits out its rate limits instead.
+    pub(crate) fn retry_policy(self) -> RetryPolicy {
+        // Called while an interaction waits
+        RetryPolicy {
+            budget: INTERACTION_WINDOW - RESPONSE_MARGIN,
+            attempt_timeout: Duration::from_millis(1000),
+            max_attempts: 3,
+            base_delay: Duration::from_millis(100),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum UpstreamError {
+    #[error("Request to {0} failed: {1}")]
+    Request(Upstream, String),
+
+    #[error("Request to {0} timed out")]
+    Timeout(Upstream),
+
+    #[error("{0} keeps failing, calls are paused for now")]
+    CircuitOpen(Upstream),
+
+    #[error("{0} answered {1}: {2}")]
+    Status(Upstream, u16, String),
+
+    #[error("Failed to deserialize the response from {0}: {1}")]
+    JsonFailed(Upstream, serde_json::Error),
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[error("No recorded response for {0} {1}")]
+    NotRecorded(Upstream, String),
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[error("Fixture {0} could not be used: {1}")]
+    Fixture(String, String),
+}
+
+/// How long calls to an upstream may take, and how they are retried.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct RetryPolicy {
+    /// The most time all attempts at one call may take together.
+    pub(crate) budget: Duration,
+    pub(crate) attempt_timeout: Duration,
+    pub(crate) max_attempts: u32,
+    /// Retries wait up to this long, doubling with every attempt.
+    pub(crate) base_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// A random wait before retry number `attempt`, so isolates retrying at
+    /// once do not all hit the upstream together.
+    fn backoff(&self, attempt: u32) -> Duration {
+        let ceiling = self.base_delay.as_millis() as u64 * 2u64.pow(attempt.saturating_sub(1));
+        Duration::from_millis(random_u64() % (ceiling + 1))
+    }
+}
+
+/// A call made through an [`UpstreamClient`], as logged with the interaction
+/// that made it.
+#[derive(Serialize, Clone, Debug)]
+pub(crate) struct UpstreamCall {
+    pub(crate) upstream: Upstream,
+    /// No attempts were made while the circuit was open.
+    pub(crate) attempts: u32,
+    pub(crate) latency_ms: u64,
+    /// The status of the last attempt, `None` when it got no response.
+    pub(crate) status: Option<u16>,
+    /// Why the call failed, cut short as upstreams may answer with whole pages.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) error: Option<String>,
+}
+
+impl UpstreamCall {
+    /// A short name for how the call went, such as `fixer_429`.
+    pub(crate) fn kind(&self) -> String {
+        match (self.status, self.attempts) {
+            (Some(status), _) => format!("{}_{}", self.upstream, status),
+            (None, 0) => format!("{}_circuit_open", self.upstream),
+            (None, _) => format!("{}_no_response", self.upstream),
+        }
+    }
+}
+
+pub(crate) struct UpstreamRequest {
+    pub(crate) upstream: Upstream,
+    pub(crate) method: String,
+    /// The path and query below the base URL, which is what recordings are keyed by.
+    pub(crate) path: String,
+    pub(crate) url: String,
+    pub(crate) headers: Vec<(String, String)>,
+}
+
+pub(crate) struct UpstreamResponse {
+    pub(crate) status: u16,
+    pub(crate) body: Vec<u8>,
+}
+
+impl UpstreamResponse {
+    fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    /// Whether the upstream itself is in trouble, rather than the request.
+    fn is_upstream_failure(&self) -> bool {
+        self.status == 429 || self.status >= 500
+    }
+}
+
+#[async_trait(?Send)]
+pub(crate) trait HttpClient {
+    async fn send(&self, request: &UpstreamRequest) -> Result<UpstreamResponse, UpstreamError>;
+}
+
+thread_local! {
+    // One client per isolate, so connections are pooled across requests
+    static SHARED_CLIENT: reqwest::Client = reqwest::Client::new();
+}
+
+/// A handle on the client shared by every upstream, cheap to clone.
+pub(crate) fn shared_client() -> reqwest::Client {
+    SHARED_CLIENT.with(Clone::clone)
+}
+
+/// Sends requests over the network.
+pub(crate) struct LiveClient {
+    client: reqwest::Client,
+}
+
+impl LiveClient {
+    pub(crate) fn new() -> Self {
+        Self {
+            client: shared_client(),
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl HttpClient for LiveClient {
+    async fn send(&self, request: &UpstreamRequest) -> Result<UpstreamResponse, UpstreamError> {
+        let method = request
+            .method
+            .parse::<reqwest::Method>()
+            .map_err(|err| UpstreamError::Request(request.upstream, err.to_string()))?;
+        let mut builder = self.client.request(method, &request.url);
+        for (key, value) in &request.headers {
+            builder = builder.header(key.as_str(), value.as_str());
+        }
+
+        let failed =
+            |err: reqwest::Error| UpstreamError::Request(request.upstream, err.to_string());
+        let response = builder.send().await.map_err(failed)?;
+        let status = response.status().as_u16();
+        let body = response.bytes().await.map_err(failed)?.to_vec();
+        Ok(UpstreamResponse { status, body })
+    }
+}
+
+/// The [`HttpClient`] picked by `UPSTREAM_MODE`: `live` (the default), `record`
+/// or `replay`, the latter two keeping fixtures below `UPSTREAM_FIXTURES`.
+/// Workers have no file system, so they always go live.
+pub(crate) fn client_for(config: &Config) -> Rc<dyn HttpClient> {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let dir = std::path::PathBuf::from(&config.upstream_fixtures);
+        match config.upstream_mode {
+            UpstreamMode::Record => {
+                return Rc::new(recording::Recorder::new(Rc::new(LiveClient::new()), dir))
+            }
+            UpstreamMode::Replay => return Rc::new(recording::Replayer::new(dir)),
+            UpstreamMode::Live => {}
+        }
+    }
+    #[cfg(target_arch = "wasm32")]
+    let _ = config;
+
+    Rc::new(LiveClient::new())
+}
+
+/// One upstream as seen from a command, cheap to clone into `'static` futures
+/// such as cache refreshes.
+#[derive(Clone)]
+pub(crate) struct UpstreamClient {
+    http: Rc<dyn HttpClient>,
+    store: Rc<dyn Store>,
+    upstream: Upstream,
+    base_url: String,
+    policy: RetryPolicy,
+    calls: Rc<RefCell<Vec<UpstreamCall>>>,
+}
+
+impl UpstreamClient {
+    pub(crate) fn new(
+        http: Rc<dyn HttpClient>,
+        store: Rc<dyn Store>,
+        upstream: Upstream,
+        base_url: String,
+    ) -> Self {
+        Self {
+            http,
+            store,
+            upstream,
+            base_url,
+            policy: upstream.retry_policy(),
+            calls: Rc::default(),
+        }
+    }
+
+    /// Notes every call in `calls`, shared with whoever logs them.
+    pub(crate) fn with_calls(mut self, calls: Rc<RefCell<Vec<UpstreamCall>>>) -> Self {
+        self.calls = calls;
+        self
+    }
+
+    #[allow(dead_code)]
+    pub(crate) fn with_policy(mut self, policy: RetryPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// GETs `path` below the base URL and deserializes the JSON response.
+    ///
+    /// Fails right away while the upstream's circuit is open.
+    pub(crate) async fn get_json<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        headers: &[(&str, &str)],
+    ) -> Result<T, UpstreamError> {
+        let request = UpstreamRequest {
+            upstream: self.upstream,
+            method: "GET".into(),
+            path: path.into(),
+            url: format!("{}{}", self.base_url, path),
+            headers: headers
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+        };
+
+        let circuit = CircuitState::load(&*self.store, self.upstream)
+            .await
+            .unwrap_or_else(|err| {
+                log_warn!("Unable to load the circuit for {} : {}", self.upstream, err);
+                CircuitState::default()
+            });
+        if circuit.is_open(chrono::Utc::now().timestamp()) {
+            let err = UpstreamError::CircuitOpen(self.upstream);
+            self.note_call(0, Duration::default(), None, Some(&err));
+            return Err(err);
+        }
+
+        let started = chrono::Utc::now();
+        let (result, attempts) = self.send_with_retries(&request).await;
+        let latency = (chrono::Utc::now() - started).to_std().unwrap_or_default();
+        let status = result.as_ref().ok().map(|response| response.status);
+        let failed = match &result {
+            Ok(response) => response.is_upstream_failure(),
+            Err(_) => true,
+        };
+        let recorded = if failed {
+            circuit
+                .record_failure(&*self.store, self.upstream, chrono::Utc::now().timestamp())
+                .await
+                .map(|_| ())
+        } else {
+            circuit.record_success(&*self.store, self.upstream).await
+        };
+        if let Err(err) = recorded {
+            log_warn!(
+                "Unable to update the circuit for {} : {}",
+                self.upstream,
+                err
+            );
+        }
+
+        let parsed = result.and_then(|response| {
+            if !response.is_success() {
+                return Err(UpstreamError::Status(
+                    self.upstream,
+                    response.status,
+                    String::from_utf8_lossy(&response.body).into_owned(),
+                ));
+            }
+            serde_json::from_slice(&response.body)
+                .map_err(|err| UpstreamError::JsonFailed(self.upstream, err))
+        });
+        self.note_call(attempts, latency, status, parsed.as_ref().err());
+        parsed
+    }
+
+    fn note_call(
+        &self,
+        attempts: u32,
+        latency: Duration,
+        status: Option<u16>,
+        error: Option<&UpstreamError>,
+    ) {
+        self.calls.borrow_mut().push(UpstreamCall {
+            upstream: self.upstream,
+            attempts,
+            latency_ms: latency.as_millis() as u64,
+            status,
+            error: error.map(|err| err.to_string().chars().take(MAX_ERROR_CHARS).collect()),
+        });
+    }
+
+    /// Sends `request`, retrying GETs that time out or find the upstream in
+    /// trouble for as long as the policy's budget allows. Returns the last
+    /// result along with the number of attempts made.
+    async fn send_with_retries(
+        &self,
+        request: &UpstreamRequest,
+    ) -> (Result<UpstreamResponse, UpstreamError>, u32) {
+        let started = chrono::Utc::now();
+        let elapsed = || (chrono::Utc::now() - started).to_std().unwrap_or_default();
+
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let remaining = self.policy.budget.saturating_sub(elapsed());
+            let result = timeout(
+                self.policy.attempt_timeout.min(remaining),
+                self.http.send(request),
+            )
+            .await
+            .unwrap_or(Err(UpstreamError::Timeout(self.upstream)));
+
+            let retryable = match &result {
+                Ok(response) => response.is_upstream_failure(),
+                Err(UpstreamError::Request(..)) | Err(UpstreamError::Timeout(_)) => true,
+                Err(_) => false,
+            };
+            // Only GETs are safe to send twice
+            if !retryable || request.method != "GET" || attempt >= self.policy.max_attempts {
+                return (result, attempt);
+            }
+
+            let delay = self.policy.backoff(attempt);
+            if elapsed() + delay >= self.policy.budget {
+                return (result, attempt);
+            }
+            log_warn!(
+                "{} {} failed on attempt {}, retrying in {}ms",
+                self.upstream,
+                request.path,
+                attempt,
+                delay.as_millis()
+            );
+            sleep(delay).await;
+        }
+    }
+}
+
+/// Builds a query string, escaping each value.
+pub(crate) fn query(params: &[(&str, &str)]) -> String {
+    let pairs = params
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, escape(value)))
+        .collect::<Vec<String>>();
+    format!("?{}", pairs.join("&"))
+}
+
+fn escape(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            byte => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) mod recording {
+    //! Fixture files hold one response each, as
+    //! `{dir}/{upstream}/{method}{path}.json` with the path made file-name safe.
+    //! Only the path below the base URL is kept, never headers, so recordings
+    //! carry no credentials and replay against any base URL.
+
+    use std::fs;
+    use std::path::PathBuf;
+    use std::rc::Rc;
+
+    use async_trait::async_trait;
+    use serde::{Deserialize, Serialize};
+    use serde_json::Value;
+
+    use super::{HttpClient, UpstreamError, UpstreamRequest, UpstreamResponse};
+
+    pub(crate) const DEFAULT_DIR: &str = "fixtures/upstream";
+
+    #[derive(Deserialize, Serialize)]
+    struct Fixture {
+        method: String,
+        path: String,
+        status: u16,
+        /// Kept as JSON when the body is JSON, so fixtures stay readable.
+        body: Value,
+    }
+
+    fn fixture_path(dir: &std::path::Path, request: &UpstreamRequest) -> PathBuf {
+        let name = format!("{}{}", request.method, request.path)
+            .chars()
+            .map(|c| {
+                if c.is_alphanumeric() || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect::<String>();
+        dir.join(request.upstream.to_string())
+            .join(format!("{}.json", name))
+    }
+
+    /// Passes requests on to another client and saves every response.
+    pub(crate) struct Recorder {
+        inner: Rc<dyn HttpClient>,
+        dir: PathBuf,
+    }
+
+    impl Recorder {
+        pub(crate) fn new(inner: Rc<dyn HttpClient>, dir: PathBuf) -> Self {
+            Self { inner, dir }
+        }
+    }
+
+    #[async_trait(?Send)]
+    impl HttpClient for Recorder {
+        async fn send(&self, request: &UpstreamRequest) -> Result<UpstreamResponse, UpstreamError> {
+            let response = self.inner.send(request).await?;
+
+            let path = fixture_path(&self.dir, request);
+            let fixture = Fixture {
+                method: request.method.clone(),
+                path: request.path.clone(),
+                status: response.status,
+                body: serde_json::from_slice(&response.body).unwrap_or_else(|_| {
+                    Value::String(String::from_utf8_lossy(&response.body).into_owned())
+                }),
+            };
+            let failed = |err: String| UpstreamError::Fixture(path.display().to_string(), err);
+            let raw =
+                serde_json::to_string_pretty(&fixture).map_err(|err| failed(err.to_string()))?;
+            path.parent()
+                .map(fs::create_dir_all)
+                .transpose()
+                .and_then(|_| fs::write(&path, raw))
+                .map_err(|err| failed(err.to_string()))?;
+            log_info!(
+                "Recorded {} {} to {}",
+                request.upstream,
+                request.path,
+                path.display()
+            );
+
+            Ok(response)
+        }
+    }
+
+    /// Answers from recorded fixtures only, failing for anything not recorded.
+    pub(crate) struct Replayer {
+        dir: PathBuf,
+    }
+
+    impl Replayer {
+        pub(crate) fn new(dir: PathBuf) -> Self {
+            Self { dir }
+        }
+    }
+
+    #[async_trait(?Send)]
+    impl HttpClient for Replayer {
+        async fn send(&self, request: &UpstreamRequest) -> Result<UpstreamResponse, UpstreamError> {
+            let path = fixture_path(&self.dir, request);
+            let raw = fs::read_to_string(&path)
+                .map_err(|_| UpstreamError::NotRecorded(request.upstream, request.path.clone()))?;
+            let fixture = serde_json::from_str::<Fixture>(&raw).map_err(|err| {
+                UpstreamError::Fixture(path.display().to_string(), err.to_string())
+            })?;
+
+            let body = match fixture.body {
+                Value::String(text) => text.into_bytes(),
+                json => json.to_string().into_bytes(),
+            };
+            Ok(UpstreamResponse {
+                status: fixture.status,
+                body,
+            })
+        }
+    }
+}
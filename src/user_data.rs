@@ -0,0 +1,116 @@
+//! Every kind of data the bot stores about a single user.
+//!
+//! Per-user keys are built through [`UserData::key`] only, so a new kind of
+//! user data has to be added here, at which point `/mydata` shows, exports and
+//! deletes it along with everything else.
+//!
+//! Guild audit log entries name whoever made a change, but belong to the
+//! guild's log rather than to the user.
+
+use std::collections::BTreeMap;
+
+use serde_json::Value;
+use strum::IntoEnumIterator;
+use strum_macros::{Display, EnumIter};
+
+use crate::audit::AuditEntry;
+use crate::preferences::UserPreferences;
+use crate::store::migration::{upgrade, Record};
+use crate::store::{list_all, Store, StoreError};
+
+#[derive(Clone, Copy, Debug, Display, EnumIter)]
+#[strum(serialize_all = "snake_case")]
+pub(crate) enum UserData {
+    /// Defaults for `/xe`.
+    Preferences,
+    /// Changes the user made to their own defaults, one key per entry.
+    AuditLog,
+}
+
+impl UserData {
+    /// The key of this data for `user_id`, or the prefix of its keys if it is
+    /// stored over several.
+    pub(crate) fn key(self, user_id: &str) -> String {
+        match self {
+            UserData::Preferences => format!("{}{}", UserPreferences::PREFIX, user_id),
+            UserData::AuditLog => format!("{}user:{}:", AuditEntry::PREFIX, user_id),
+        }
+    }
+
+    fn is_prefix(self) -> bool {
+        match self {
+            UserData::Preferences => false,
+            UserData::AuditLog => true,
+        }
+    }
+
+    /// Every key currently stored for `user_id`.
+    async fn stored_keys(
+        self,
+        store: &dyn Store,
+        user_id: &str,
+    ) -> Result<Vec<String>, StoreError> {
+        let key = self.key(user_id);
+        if self.is_prefix() {
+            return list_all(store, &key).await;
+        }
+        Ok(match store.get(&key).await? {
+            Some(_) => vec![key],
+            None => vec![],
+        })
+    }
+}
+
+/// Everything stored for `user_id`, by kind, with the stored JSON as it is.
+pub(crate) async fn collect(
+    store: &dyn Store,
+    user_id: &str,
+) -> Result<BTreeMap<String, Value>, StoreError> {
+    let mut data = BTreeMap::new();
+    for kind in UserData::iter() {
+        let mut values = Vec::new();
+        for key in kind.stored_keys(store, user_id).await? {
+            if let Some(raw) = store.get(&key).await? {
+                // Keep whatever is there, even if it is not valid JSON
+                values.push(serde_json::from_str(&raw).unwrap_or(Value::String(raw)));
+            }
+        }
+
+        // An empty preferences record only marks the legacy keys as checked
+        if let UserData::Preferences = kind {
+            values.retain(|value| {
+                upgrade::<UserPreferences>(value.clone()).map_or(true, |preferences| {
+                    preferences != UserPreferences::default()
+                })
+            });
+        }
+        if values.is_empty() {
+            continue;
+        }
+        let value = if kind.is_prefix() {
+            Value::Array(values)
+        } else {
+            values.remove(0)
+        };
+        data.insert(kind.to_string(), value);
+    }
+    Ok(data)
+}
+
+/// Deletes everything stored for `user_id`, returning the kinds that were stored.
+pub(crate) async fn delete_all(
+    store: &dyn Store,
+    user_id: &str,
+) -> Result<Vec<String>, StoreError> {
+    let mut deleted = Vec::new();
+    for kind in UserData::iter() {
+        let keys = kind.stored_keys(store, user_id).await?;
+        for key in &keys {
+            store.delete(key).await?;
+        }
+        if !keys.is_empty() {
+            deleted.push(kind.to_string());
+        }
+    }
+    Ok(deleted)
+}
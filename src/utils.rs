@@ -1,3 +1,7 @@
+use std::future::Future;
+use std::task::Poll;
+use std::time::Duration;
+
 use cfg_if::cfg_if;
 
 cfg_if! {
@@ -11,3 +15,38 @@ cfg_if! {
     }
 }
 
+/// Waits for `duration` on whichever runtime the bot is running on.
+#[cfg(target_arch = "wasm32")]
+pub(crate) async fn sleep(duration: Duration) {
+    worker::Delay::from(duration).await
+}
+
+/// Waits for `duration` on whichever runtime the bot is running on.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) async fn sleep(duration: Duration) {
+    tokio::time::sleep(duration).await
+}
+
+/// Runs `future` for at most `duration`, `None` when it did not finish in time.
+pub(crate) async fn timeout<F: Future>(duration: Duration, future: F) -> Option<F::Output> {
+    let mut future = Box::pin(future);
+    let mut timer = Box::pin(sleep(duration));
+    std::future::poll_fn(|cx| {
+        if let Poll::Ready(output) = future.as_mut().poll(cx) {
+            return Poll::Ready(Some(output));
+        }
+        timer.as_mut().poll(cx).map(|_| None)
+    })
+    .await
+}
+
+/// A random number from the platform's secure source, `crypto.getRandomValues`
+/// on Workers. The clock is only precise to the millisecond there, so it cannot
+/// stand in for one.
+pub(crate) fn random_u64() -> u64 {
+    let mut bytes = [0; 8];
+    if let Err(err) = getrandom::getrandom(&mut bytes) {
+        log_warn!("Unable to get random bytes : {}", err);
+    }
+    u64::from_le_bytes(bytes)
+}
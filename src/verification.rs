@@ -1,3 +1,6 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
 use ed25519_dalek::{PublicKey, Signature, SignatureError, Verifier};
 use hex::FromHexError;
 
@@ -11,26 +14,96 @@ pub(crate) enum VerificationError {
 
     #[error("Invalid signature provided.")]
     InvalidSignature(ed25519_dalek::ed25519::Error),
+
+    #[error("Replayed request rejected: {0}.")]
+    ReplayRejected(String),
+}
+
+thread_local! {
+    // Parsed keys along with the raw setting they came from, so a changed
+    // setting is picked up without parsing on every request
+    static PUBLIC_KEYS: RefCell<Option<(String, Rc<Vec<PublicKey>>)>> = const { RefCell::new(None) };
+}
+
+/// Parses a comma separated list of hex encoded public keys.
+///
+/// More than one key is accepted so the application key can be rotated without
+/// rejecting interactions signed with the old one in the meantime.
+pub(crate) fn parse_public_keys(public_keys: &str) -> Result<Vec<PublicKey>, VerificationError> {
+    let keys = public_keys
+        .split(',')
+        .map(str::trim)
+        .filter(|key| !key.is_empty())
+        .map(|key| Ok(PublicKey::from_bytes(&hex::decode(key)?)?))
+        .collect::<Result<Vec<PublicKey>, VerificationError>>()?;
+
+    if keys.is_empty() {
+        return Err(VerificationError::InvalidPublicKey(SignatureError::new()));
+    }
+
+    Ok(keys)
+}
+
+/// Like [`parse_public_keys`], but parses each distinct setting only once per isolate.
+pub(crate) fn cached_public_keys(
+    public_keys: &str,
+) -> Result<Rc<Vec<PublicKey>>, VerificationError> {
+    let cached = PUBLIC_KEYS.with(|cache| match &*cache.borrow() {
+        Some((raw, keys)) if raw == public_keys => Some(keys.clone()),
+        _ => None,
+    });
+    if let Some(keys) = cached {
+        return Ok(keys);
+    }
+
+    let keys = Rc::new(parse_public_keys(public_keys)?);
+    PUBLIC_KEYS.with(|cache| {
+        *cache.borrow_mut() = Some((public_keys.to_string(), keys.clone()));
+    });
+    Ok(keys)
 }
 
+/// Accepts the request when any of `public_keys` verifies the signature.
 pub(crate) fn verify_signature(
-    public_key: &str,
+    public_keys: &[PublicKey],
     signature: &str,
     timestamp: &str,
     body: &str,
 ) -> Result<(), VerificationError> {
-    let public_key = &hex::decode(public_key)
+    let signature = hex::decode(signature)
         .map_err(VerificationError::ParseHexFailed)
         .and_then(|bytes| {
-            PublicKey::from_bytes(&bytes).map_err(VerificationError::InvalidSignature)
+            Signature::from_bytes(&bytes).map_err(VerificationError::InvalidSignature)
         })?;
+    let message = format!("{}{}", timestamp, body);
 
-    Ok(public_key.verify(
-        format!("{}{}", timestamp, body).as_bytes(),
-        &hex::decode(&signature)
-            .map_err(VerificationError::ParseHexFailed)
-            .and_then(|bytes| {
-                Signature::from_bytes(&bytes).map_err(VerificationError::InvalidSignature)
-            })?,
-    )?)
-}
\ No newline at end of file
+    let mut result = Err(VerificationError::InvalidSignature(SignatureError::new()));
+    for public_key in public_keys {
+        match public_key.verify(message.as_bytes(), &signature) {
+            Ok(()) => return Ok(()),
+            Err(err) => result = Err(VerificationError::InvalidSignature(err)),
+        }
+    }
+    result
+}
+
+/// Rejects requests whose `x-signature-timestamp` is more than `max_age` seconds
+/// away from `now`, so a captured request cannot be replayed later on.
+pub(crate) fn verify_timestamp(
+    timestamp: &str,
+    now: i64,
+    max_age: i64,
+) -> Result<(), VerificationError> {
+    let timestamp = timestamp
+        .parse::<i64>()
+        .map_err(|_| VerificationError::ReplayRejected("timestamp is not a number".into()))?;
+
+    if (now - timestamp).abs() > max_age {
+        return Err(VerificationError::ReplayRejected(format!(
+            "timestamp is outside the {} second window",
+            max_age
+        )));
+    }
+
+    Ok(())
+}